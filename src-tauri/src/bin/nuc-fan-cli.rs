@@ -0,0 +1,570 @@
+use std::{
+    env,
+    thread,
+    process,
+    sync::{Arc, atomic::{AtomicBool, Ordering}},
+};
+use serde_json::{json, Value};
+use x15_71fan::modules::{
+    explorer::{
+        default_snapshot_export_path, delete_snapshot, diff_live, diff_snapshots, dump_registers, export_snapshot,
+        import_snapshot, list_snapshots, load_snapshot, read_snapshot, register_labels, save_snapshot, RegisterRange,
+    },
+    device::{current_device, device_init, require_feature},
+    ec::{ec_init, ec_open, ec_read, ec_write, parse_addr, parse_byte, parse_num, use_simulator, WriteReport},
+    fan::{fan_curve, fan_init, fan_mode_state, fan_reset, fan_set, read_fan_mode, read_sensors},
+    tdp::{
+        list_power_profiles, load_power_profile, read_mode_defaults, read_nv_oc, read_power_limits,
+        restore_mode_defaults, save_power_profile, write_nv_oc, write_power_limits, read_tdp, write_tdp,
+    },
+    profile::{list_profiles, load_profile},
+    config::load_poll_config,
+    poll::PollScheduler,
+    struct_set::{ChargeSchedule, LightbarState, NvOc, Rgb, RgbPreset, Tdp, VendorFanSettings},
+    ipc::IpcClient,
+    battery::{read_battery, set_charge_limit},
+    charge::{read_charge_schedule, set_charge_schedule},
+    power::read_power_status,
+    lightbar::{read_lightbar, set_lightbar},
+    vendor_fan::{disable_vendor_fan, preview_fan_table, program_fan_table, read_vendor_fan, set_vendor_fan},
+    rgb::{
+        apply_rgb_preset, delete_rgb_preset, list_rgb_presets, read_keyboard_rgb, restore_keyboard_rgb, save_rgb_preset,
+        set_keyboard_rgb, step_keyboard_brightness, RgbTarget,
+    },
+};
+
+const USAGE: &str = "用法: nuc-fan-cli [--json] [--sim] [--direct] [--allow-unknown] <命令>
+
+nuc-fan-daemon 运行时命令通过 IPC 交给守护进程执行，--direct 强制直接访问 EC
+未识别的机型默认拒绝写 EC，--allow-unknown 确认兼容后强制运行
+
+命令:
+  device                                  读取机型识别信息和能力
+  status                                  读取温度、转速和功耗
+  battery                                 读取电池电量、健康度和充放电状态
+  battery limit <percent>                 设置充电上限(50/60/70/80/90/100)，启动守护进程时自动恢复
+  battery schedule                        读取双速率充电设置
+  battery schedule <first> <second> <start> <end>
+                                          start~end(HH:MM)内按 second% 速率充电，其余时间按 first%
+  power                                   读取供电方式和 Type-C 适配器功率
+  fan set <left> <right>                  设置左右风扇转速(0~100)
+  fan auto                                恢复 EC 自动控制
+  fan mode                                读取风扇控制模式(0x751)和恢复状态
+  fan vendor                              读取固件风扇模式和五档曲线
+  fan vendor set <mode> <l1> <l2> <l3> <l4> <l5>
+                                          切换到固件风扇模式(0~3)并写入五档 PWM，应用退出后仍有效
+                                          l1 与 TCC 偏移同址(1926)，机型条目未设 reg_1926 为 myfan3_l1 时须等于当前值
+  fan vendor fit <name> <mode>            预览风扇配置换算成固件五档表的结果和误差，
+                                          机型条目没有 fan_breakpoints 时按等分温度点估算
+  fan vendor program <name> <mode>        把风扇配置写入固件五档表并校验，应用退出后仍有效
+  fan vendor off                          关闭固件风扇模式
+  profile list                            列出已保存的风扇配置
+  profile apply <name>                    按配置曲线调速；无守护进程时前台运行，Ctrl+C 退出并恢复自动
+  profile stop                            停止守护进程的曲线调速并恢复自动
+  tdp get                                 读取 TDP
+  tdp set <cpu1> <cpu2> <gpu1> <gpu2> <tcc>
+  limits                                  读取 PL1/PL2/PL4/PSYS PL1/GPU/TCC 功耗设置
+  limits defaults                         读取各模式出厂默认值
+  limits restore <mode>                   恢复某个模式(0/1/2)的出厂 PL1/PL2/PL4
+  gpu oc                                  读取显卡超频/TGP 设置
+  gpu oc <on|off> <ctgp> <cpu_target> <max_tgp>
+                                          设置显卡超频开关和 CTGP/CPU 功耗目标/最大 TGP(W)
+  limits profile list                     列出已保存的功耗配置
+  limits profile save <name>              把当前功耗设置保存为配置
+  limits profile apply <name>             应用功耗配置
+  rgb                                     读取键盘灯颜色(接电源/电池/出厂)
+  rgb set <ac|battery> <r> <g> <b>        设置键盘灯颜色
+  rgb up|down                             调节键盘灯亮度
+  rgb default                             恢复出厂颜色
+  rgb preset list                         列出键盘灯预设
+  rgb preset save <name>                  把当前颜色保存为预设
+  rgb preset apply <name>                 应用预设，启动守护进程时自动恢复
+  rgb preset delete <name>                删除预设
+  lightbar                                读取灯条状态
+  lightbar off                            关闭灯条
+  lightbar set <r> <g> <b>                打开灯条并设置颜色(会关闭温度指示)
+  lightbar thermal on|off                 开关灯条温度指示(需要守护进程)
+  ec read <addr>                          读取 EC 寄存器(支持 0x 十六进制)
+  ec write <addr> <value>                 写入 EC 寄存器
+  ec dump <start> <len>                   只读方式读取一段寄存器并按寄存器表标注
+  ec labels                               列出寄存器表
+  ec snapshot list                        列出已保存的寄存器快照
+  ec snapshot take <name> [<start> <len>] 保存快照，未给出范围时记录寄存器表中的全部地址
+  ec snapshot show|delete <name>          查看或删除快照
+  ec snapshot diff <from> [to]            比较两个快照，未给出 to 时和 EC 当前值比较
+  ec snapshot export <name> [path]        导出快照文件
+  ec snapshot import <path> [name]        导入快照文件
+                                          守护进程运行时 path 为配置目录 exports 下的相对路径";
+
+fn parse_percent(s: &str) -> Result<i16, String> {
+    match s.parse::<i16>() {
+        Ok(v) if (0..=100).contains(&v) => Ok(v),
+        _ => Err(format!("转速超出范围 0~100: {}", s)),
+    }
+}
+
+fn parse_i64(s: &str) -> Result<i64, String> {
+    s.parse::<i64>().map_err(|_| format!("无效的数值: {}", s))
+}
+
+fn parse_schedule(first: &str, second: &str, start: &str, end: &str) -> Result<ChargeSchedule, String> {
+    Ok(ChargeSchedule {
+        first_rate: parse_byte(first)?,
+        second_rate: parse_byte(second)?,
+        second_start: start.to_string(),
+        second_end: end.to_string(),
+    })
+}
+
+// 写入校验失败时按错误退出，输出失败的寄存器
+fn verified(value: Value) -> Result<Value, String> {
+    if value.get("ok") == Some(&Value::Bool(false)) {
+        let report: WriteReport = serde_json::from_value(value.clone()).map_err(|e| e.to_string())?;
+        report.check()?;
+    }
+    Ok(value)
+}
+
+fn parse_target(s: &str) -> Result<RgbTarget, String> {
+    match s {
+        "ac" => Ok(RgbTarget::Ac),
+        "battery" => Ok(RgbTarget::Battery),
+        _ => Err(format!("只能是 ac 或 battery: {}", s)),
+    }
+}
+
+fn parse_rgb(r: &str, g: &str, b: &str) -> Result<Rgb, String> {
+    Ok(Rgb { r: parse_byte(r)?, g: parse_byte(g)?, b: parse_byte(b)? })
+}
+
+fn parse_nv_oc(mode: &str, ctgp: &str, cpu_target: &str, max_tgp: &str) -> Result<NvOc, String> {
+    let enabled = match mode {
+        "on" => true,
+        "off" => false,
+        _ => return Err(format!("只能是 on 或 off: {}", mode)),
+    };
+    Ok(NvOc {
+        enabled,
+        ctgp: parse_byte(ctgp)?,
+        cpu_power_target: parse_byte(cpu_target)?,
+        max_tgp: parse_byte(max_tgp)?,
+    })
+}
+
+// MYFANI 参数沿用 EC 当前值
+fn parse_vendor_fan(current: &Value, mode: &str, levels: [&str; 5]) -> Result<VendorFanSettings, String> {
+    let byte = |key: &str| current.get(key).and_then(|v| v.as_u64()).unwrap_or(0) as u8;
+    let mut curve = [0u8; 5];
+    for (pwm, level) in curve.iter_mut().zip(levels) {
+        *pwm = parse_byte(level)?;
+    }
+    Ok(VendorFanSettings {
+        mode: parse_byte(mode)?,
+        curve,
+        min_speed: byte("min_speed"),
+        min_temp: byte("min_temp"),
+        extra_speed: byte("extra_speed"),
+    })
+}
+
+fn parse_range(start: &str, len: &str) -> Result<RegisterRange, String> {
+    let len = parse_num(len)?;
+    Ok(RegisterRange { start: parse_addr(start)?, len: u32::try_from(len).map_err(|_| format!("无效的数值: {}", len))? })
+}
+
+fn status() -> Result<Value, String> {
+    let ec = ec_open();
+    let sample = read_sensors(ec.as_ref());
+    let fan_mode = read_fan_mode(ec.as_ref());
+    Ok(json!({
+        "cpu_temp": sample.cpu_temp,
+        "gpu_temp": sample.gpu_temp,
+        "left_fan_speed": sample.left_fan_speed,
+        "right_fan_speed": sample.right_fan_speed,
+        "apc_watt": sample.apc_watt,
+        "cpu_pl1": sample.cpu_pl1,
+        "cpu_pl2": sample.cpu_pl2,
+        "fan_mode": fan_mode,
+    }))
+}
+
+// 前台运行风扇曲线，直到 Ctrl+C
+fn profile_apply(name: &str, json_out: bool) -> Result<Value, String> {
+    let fan_data = serde_json::to_value(load_profile(name)?).map_err(|e| e.to_string())?;
+    let running = Arc::new(AtomicBool::new(true));
+    let flag = Arc::clone(&running);
+    thread::spawn(move || {
+        let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        if rt.block_on(tokio::signal::ctrl_c()).is_ok() {
+            flag.store(false, Ordering::SeqCst);
+        }
+    });
+    let poll_config = load_poll_config();
+    let mut scheduler = PollScheduler::new();
+    let ec = ec_open();
+    fan_init(ec.as_ref());
+    while running.load(Ordering::SeqCst) {
+        let sample = read_sensors(ec.as_ref());
+        let duty = fan_curve(ec.as_ref(), &fan_data.get("left_fan"), &fan_data.get("right_fan"), sample.cpu_temp, sample.gpu_temp);
+        if json_out {
+            println!("{}", json!({ "cpu_temp": sample.cpu_temp, "gpu_temp": sample.gpu_temp, "duty": duty }));
+        }
+        thread::sleep(scheduler.next_interval(&poll_config, sample.cpu_temp, sample.gpu_temp));
+    }
+    fan_reset(ec.as_ref());
+    Ok(json!({ "profile": name, "stopped": true }))
+}
+
+// 命令对应的机型功能名，和 RPC 方法前缀一致
+fn feature(args: &[&str]) -> &'static str {
+    match args {
+        ["battery", ..] => "battery",
+        ["power", ..] => "power",
+        ["tdp", ..] => "tdp",
+        ["limits", "profile", ..] => "power_profile",
+        ["limits", ..] => "limits",
+        ["gpu", ..] => "nv_oc",
+        ["rgb", ..] => "rgb",
+        ["lightbar", ..] => "lightbar",
+        ["fan", "vendor", ..] => "vendor_fan",
+        _ => "",
+    }
+}
+
+fn run(args: &[&str], json_out: bool) -> Result<Value, String> {
+    require_feature(feature(args))?;
+    match args {
+        ["device"] => serde_json::to_value(current_device()).map_err(|e| e.to_string()),
+        ["status"] => status(),
+        ["battery"] => serde_json::to_value(read_battery(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["battery", "limit", limit] => {
+            let limit = parse_byte(limit)?;
+            set_charge_limit(ec_open().as_ref(), limit)?;
+            Ok(json!({ "charge_limit": limit }))
+        }
+        ["battery", "schedule"] => serde_json::to_value(read_charge_schedule(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["battery", "schedule", first, second, start, end] => {
+            let schedule = parse_schedule(first, second, start, end)?;
+            set_charge_schedule(ec_open().as_ref(), &schedule)?;
+            serde_json::to_value(schedule).map_err(|e| e.to_string())
+        }
+        ["power"] => serde_json::to_value(read_power_status(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["fan", "set", left, right] => {
+            let (left, right) = (parse_percent(left)?, parse_percent(right)?);
+            let ec = ec_open();
+            fan_init(ec.as_ref());
+            let report = fan_set(ec.as_ref(), left, right)?;
+            verified(json!({ "left": left, "right": right, "ok": report.ok, "writes": report.writes }))
+        }
+        ["fan", "auto"] => {
+            fan_reset(ec_open().as_ref());
+            Ok(json!({ "mode": "auto" }))
+        }
+        ["fan", "mode"] => Ok(json!({ "mode": read_fan_mode(ec_open().as_ref()), "state": fan_mode_state() })),
+        ["fan", "vendor"] => serde_json::to_value(read_vendor_fan(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["fan", "vendor", "set", mode, l1, l2, l3, l4, l5] => {
+            let ec = ec_open();
+            let current = serde_json::to_value(read_vendor_fan(ec.as_ref())).map_err(|e| e.to_string())?;
+            let settings = parse_vendor_fan(&current, mode, [l1, l2, l3, l4, l5])?;
+            set_vendor_fan(ec.as_ref(), &settings)?;
+            serde_json::to_value(settings).map_err(|e| e.to_string())
+        }
+        ["fan", "vendor", "fit", name, mode] => {
+            serde_json::to_value(preview_fan_table(ec_open().as_ref(), name, parse_byte(mode)?)?).map_err(|e| e.to_string())
+        }
+        ["fan", "vendor", "program", name, mode] => {
+            let ec = ec_open();
+            let mode = parse_byte(mode)?;
+            preview_fan_table(ec.as_ref(), name, mode)?;
+            serde_json::to_value(program_fan_table(ec.as_ref(), name, mode)?).map_err(|e| e.to_string())
+        }
+        ["fan", "vendor", "off"] => {
+            disable_vendor_fan(ec_open().as_ref())?;
+            Ok(Value::Null)
+        }
+        ["profile", "list"] => Ok(json!(list_profiles())),
+        ["profile", "apply", name] => profile_apply(name, json_out),
+        ["profile", "stop"] => Err("profile stop 需要 nuc-fan-daemon 在运行".to_string()),
+        ["tdp", "get"] => serde_json::to_value(read_tdp(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["tdp", "set", cpu1, cpu2, gpu1, gpu2, tcc] => {
+            let t = Tdp {
+                cpu1: parse_i64(cpu1)?,
+                cpu2: parse_i64(cpu2)?,
+                gpu1: parse_i64(gpu1)?,
+                gpu2: parse_i64(gpu2)?,
+                tcc: parse_i64(tcc)?,
+            };
+            verified(serde_json::to_value(write_tdp(ec_open().as_ref(), &t)?).map_err(|e| e.to_string())?)
+        }
+        ["limits"] => serde_json::to_value(read_power_limits(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["limits", "defaults"] => serde_json::to_value(read_mode_defaults(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["limits", "restore", mode] => {
+            let limits = restore_mode_defaults(ec_open().as_ref(), parse_byte(mode)?)?;
+            serde_json::to_value(limits).map_err(|e| e.to_string())
+        }
+        ["gpu", "oc"] => serde_json::to_value(read_nv_oc(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["gpu", "oc", mode, ctgp, cpu_target, max_tgp] => {
+            let report = write_nv_oc(ec_open().as_ref(), &parse_nv_oc(mode, ctgp, cpu_target, max_tgp)?)?;
+            verified(serde_json::to_value(report).map_err(|e| e.to_string())?)
+        }
+        ["limits", "profile", "list"] => Ok(json!(list_power_profiles())),
+        ["limits", "profile", "save", name] => {
+            let limits = read_power_limits(ec_open().as_ref());
+            save_power_profile(name, &limits)?;
+            serde_json::to_value(limits).map_err(|e| e.to_string())
+        }
+        ["limits", "profile", "apply", name] => {
+            let limits = load_power_profile(name)?;
+            let report = write_power_limits(ec_open().as_ref(), &limits)?;
+            verified(serde_json::to_value(report).map_err(|e| e.to_string())?)
+        }
+        ["rgb"] => serde_json::to_value(read_keyboard_rgb(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["rgb", "set", target, r, g, b] => {
+            let (target, rgb) = (parse_target(target)?, parse_rgb(r, g, b)?);
+            set_keyboard_rgb(ec_open().as_ref(), target, rgb)?;
+            serde_json::to_value(rgb).map_err(|e| e.to_string())
+        }
+        ["rgb", direction @ ("up" | "down")] => {
+            step_keyboard_brightness(ec_open().as_ref(), *direction == "up");
+            Ok(Value::Null)
+        }
+        ["rgb", "default"] => serde_json::to_value(restore_keyboard_rgb(ec_open().as_ref())?).map_err(|e| e.to_string()),
+        ["rgb", "preset", "list"] => Ok(json!(list_rgb_presets())),
+        ["rgb", "preset", "save", name] => {
+            let rgb = read_keyboard_rgb(ec_open().as_ref());
+            let preset = RgbPreset { ac: rgb.ac, battery: rgb.battery };
+            save_rgb_preset(name, &preset)?;
+            serde_json::to_value(preset).map_err(|e| e.to_string())
+        }
+        ["rgb", "preset", "apply", name] => serde_json::to_value(apply_rgb_preset(ec_open().as_ref(), name)?).map_err(|e| e.to_string()),
+        ["rgb", "preset", "delete", name] => {
+            delete_rgb_preset(name)?;
+            Ok(Value::Null)
+        }
+        ["lightbar"] => serde_json::to_value(read_lightbar(ec_open().as_ref())).map_err(|e| e.to_string()),
+        ["lightbar", "off"] => {
+            let state = LightbarState { on: false, color: read_lightbar(ec_open().as_ref()).color };
+            set_lightbar(ec_open().as_ref(), &state)?;
+            serde_json::to_value(state).map_err(|e| e.to_string())
+        }
+        ["lightbar", "set", r, g, b] => {
+            let state = LightbarState { on: true, color: parse_rgb(r, g, b)? };
+            set_lightbar(ec_open().as_ref(), &state)?;
+            serde_json::to_value(state).map_err(|e| e.to_string())
+        }
+        ["lightbar", "thermal", _] => Err("温度指示由控制循环驱动，需要 nuc-fan-daemon 在运行".to_string()),
+        ["ec", "read", addr] => {
+            let addr = parse_addr(addr)?;
+            Ok(json!({ "addr": addr, "value": ec_read(ec_open().as_ref(), addr) }))
+        }
+        ["ec", "write", addr, value] => {
+            let (addr, value) = (parse_addr(addr)?, parse_byte(value)?);
+            let ec = ec_open();
+            let out = ec_write(ec.as_ref(), addr, value);
+            Ok(json!({ "addr": addr, "value": value, "return": out, "read_back": ec_read(ec.as_ref(), addr) }))
+        }
+        ["ec", "dump", start, len] => {
+            let range = parse_range(start, len)?;
+            serde_json::to_value(dump_registers(ec_open().as_ref(), &range)?).map_err(|e| e.to_string())
+        }
+        ["ec", "labels"] => serde_json::to_value(register_labels()).map_err(|e| e.to_string()),
+        ["ec", "snapshot", "list"] => Ok(json!(list_snapshots())),
+        ["ec", "snapshot", "take", name, range @ ..] => {
+            let ranges = match range {
+                [] => Vec::new(),
+                [start, len] => vec![parse_range(start, len)?],
+                _ => return Err(USAGE.to_string()),
+            };
+            let snapshot = read_snapshot(ec_open().as_ref(), name, &ranges)?;
+            save_snapshot(&snapshot)?;
+            serde_json::to_value(snapshot).map_err(|e| e.to_string())
+        }
+        ["ec", "snapshot", "show", name] => serde_json::to_value(load_snapshot(name)?).map_err(|e| e.to_string()),
+        ["ec", "snapshot", "delete", name] => {
+            delete_snapshot(name)?;
+            Ok(Value::Null)
+        }
+        ["ec", "snapshot", "diff", from] => {
+            serde_json::to_value(diff_live(ec_open().as_ref(), &load_snapshot(from)?)).map_err(|e| e.to_string())
+        }
+        ["ec", "snapshot", "diff", from, to] => {
+            serde_json::to_value(diff_snapshots(&load_snapshot(from)?, &load_snapshot(to)?)).map_err(|e| e.to_string())
+        }
+        ["ec", "snapshot", "export", name, path @ ..] => {
+            let path = match path {
+                [] => default_snapshot_export_path(name)?,
+                [path] => path.into(),
+                _ => return Err(USAGE.to_string()),
+            };
+            export_snapshot(name, &path)?;
+            Ok(json!({ "path": path.to_string_lossy() }))
+        }
+        ["ec", "snapshot", "import", path] => {
+            serde_json::to_value(import_snapshot(path.as_ref(), None)?).map_err(|e| e.to_string())
+        }
+        ["ec", "snapshot", "import", path, name] => {
+            serde_json::to_value(import_snapshot(path.as_ref(), Some(name))?).map_err(|e| e.to_string())
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+// 守护进程在运行时转发给它，避免和控制循环同时写 EC
+fn run_remote(client: &mut IpcClient, args: &[&str]) -> Result<Value, String> {
+    match args {
+        ["device"] => client.call("device.get", Value::Null),
+        ["status"] => client.call("status", Value::Null),
+        ["battery"] => client.call("battery.get", Value::Null),
+        ["battery", "limit", limit] => client.call("battery.set_charge_limit", json!({ "limit": parse_byte(limit)? })),
+        ["battery", "schedule"] => client.call("battery.get_charge_schedule", Value::Null),
+        ["battery", "schedule", first, second, start, end] => {
+            client.call("battery.set_charge_schedule", json!({ "schedule": parse_schedule(first, second, start, end)? }))
+        }
+        ["power"] => client.call("power.get", Value::Null),
+        ["fan", "set", left, right] => {
+            verified(client.call("fan.set", json!({ "left": parse_percent(left)?, "right": parse_percent(right)? }))?)
+        }
+        ["fan", "auto"] => client.call("fan.auto", Value::Null),
+        ["fan", "mode"] => client.call("fan.mode", Value::Null),
+        ["fan", "vendor"] => client.call("vendor_fan.get", Value::Null),
+        ["fan", "vendor", "set", mode, l1, l2, l3, l4, l5] => {
+            let current = client.call("vendor_fan.get", Value::Null)?;
+            let settings = parse_vendor_fan(&current, mode, [l1, l2, l3, l4, l5])?;
+            client.call("vendor_fan.set", json!({ "settings": settings }))
+        }
+        ["fan", "vendor", "fit", name, mode] => {
+            client.call("vendor_fan.fit", json!({ "name": name, "mode": parse_byte(mode)? }))
+        }
+        ["fan", "vendor", "program", name, mode] => {
+            client.call("vendor_fan.program", json!({ "name": name, "mode": parse_byte(mode)? }))
+        }
+        ["fan", "vendor", "off"] => client.call("vendor_fan.disable", Value::Null),
+        ["profile", "list"] => client.call("profile.list", Value::Null),
+        ["profile", "apply", name] => client.call("profile.apply", json!({ "name": name })),
+        ["profile", "stop"] => client.call("profile.stop", Value::Null),
+        ["tdp", "get"] => client.call("tdp.get", Value::Null),
+        ["tdp", "set", cpu1, cpu2, gpu1, gpu2, tcc] => {
+            let t = Tdp {
+                cpu1: parse_i64(cpu1)?,
+                cpu2: parse_i64(cpu2)?,
+                gpu1: parse_i64(gpu1)?,
+                gpu2: parse_i64(gpu2)?,
+                tcc: parse_i64(tcc)?,
+            };
+            verified(client.call("tdp.set", json!({ "tdp": t }))?)
+        }
+        ["limits"] => client.call("limits.get", Value::Null),
+        ["limits", "defaults"] => client.call("limits.defaults", Value::Null),
+        ["limits", "restore", mode] => client.call("limits.restore", json!({ "mode": parse_byte(mode)? })),
+        ["gpu", "oc"] => client.call("nv_oc.get", Value::Null),
+        ["gpu", "oc", mode, ctgp, cpu_target, max_tgp] => {
+            verified(client.call("nv_oc.set", json!({ "config": parse_nv_oc(mode, ctgp, cpu_target, max_tgp)? }))?)
+        }
+        ["limits", "profile", "list"] => client.call("power_profile.list", Value::Null),
+        ["limits", "profile", "save", name] => client.call("power_profile.save", json!({ "name": name })),
+        ["limits", "profile", "apply", name] => verified(client.call("power_profile.apply", json!({ "name": name }))?),
+        ["rgb"] => client.call("rgb.get", Value::Null),
+        ["rgb", "set", target, r, g, b] => {
+            client.call("rgb.set", json!({ "target": parse_target(target)?, "color": parse_rgb(r, g, b)? }))
+        }
+        ["rgb", direction @ ("up" | "down")] => client.call("rgb.brightness", json!({ "direction": direction })),
+        ["rgb", "default"] => client.call("rgb.restore_default", Value::Null),
+        ["rgb", "preset", "list"] => client.call("rgb.preset.list", Value::Null),
+        ["rgb", "preset", "save", name] => client.call("rgb.preset.save", json!({ "name": name })),
+        ["rgb", "preset", "apply", name] => client.call("rgb.preset.apply", json!({ "name": name })),
+        ["rgb", "preset", "delete", name] => client.call("rgb.preset.delete", json!({ "name": name })),
+        ["lightbar"] => client.call("lightbar.get", Value::Null),
+        ["lightbar", "off"] => {
+            let color = client.call("lightbar.get", Value::Null)?.get("color").cloned();
+            client.call("lightbar.set", json!({ "on": false, "color": color }))
+        }
+        ["lightbar", "set", r, g, b] => client.call("lightbar.set", json!({ "on": true, "color": parse_rgb(r, g, b)? })),
+        ["lightbar", "thermal", mode @ ("on" | "off")] => {
+            let mut config = client.call("lightbar.thermal.get", Value::Null)?;
+            config["enabled"] = json!(*mode == "on");
+            client.call("lightbar.thermal.set", json!({ "config": config }))?;
+            client.call("lightbar.thermal.get", Value::Null)
+        }
+        ["ec", "read", addr] => client.call("ec.read", json!({ "addr": parse_addr(addr)? })),
+        ["ec", "write", addr, value] => {
+            client.call("ec.write", json!({ "addr": parse_addr(addr)?, "value": parse_byte(value)? }))
+        }
+        ["ec", "dump", start, len] => {
+            let range = parse_range(start, len)?;
+            client.call("ec.dump", json!({ "start": range.start, "len": range.len }))
+        }
+        ["ec", "labels"] => client.call("ec.labels", Value::Null),
+        ["ec", "snapshot", "list"] => client.call("ec.snapshot.list", Value::Null),
+        ["ec", "snapshot", "take", name] => client.call("ec.snapshot.take", json!({ "name": name })),
+        ["ec", "snapshot", "take", name, start, len] => {
+            client.call("ec.snapshot.take", json!({ "name": name, "ranges": [parse_range(start, len)?] }))
+        }
+        ["ec", "snapshot", "show", name] => client.call("ec.snapshot.get", json!({ "name": name })),
+        ["ec", "snapshot", "delete", name] => client.call("ec.snapshot.delete", json!({ "name": name })),
+        ["ec", "snapshot", "diff", from] => client.call("ec.snapshot.diff", json!({ "from": from })),
+        ["ec", "snapshot", "diff", from, to] => client.call("ec.snapshot.diff", json!({ "from": from, "to": to })),
+        ["ec", "snapshot", "export", name] => client.call("ec.snapshot.export", json!({ "name": name })),
+        // 守护进程只接受 exports 目录下的相对路径
+        ["ec", "snapshot", "export", name, path] => client.call("ec.snapshot.export", json!({ "name": name, "path": path })),
+        ["ec", "snapshot", "import", path] => client.call("ec.snapshot.import", json!({ "path": path })),
+        ["ec", "snapshot", "import", path, name] => {
+            client.call("ec.snapshot.import", json!({ "path": path, "name": name }))
+        }
+        _ => Err(USAGE.to_string()),
+    }
+}
+
+fn print_text(value: &Value) {
+    match value {
+        Value::Object(map) => {
+            for (k, v) in map {
+                println!("{}: {}", k, v);
+            }
+        }
+        Value::Array(items) => {
+            for item in items {
+                println!("{}", item.as_str().map(|s| s.to_string()).unwrap_or_else(|| item.to_string()));
+            }
+        }
+        v => println!("{}", v),
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    let json_out = args.iter().any(|a| a == "--json");
+    let direct = args.iter().any(|a| a == "--direct");
+    let allow_unknown = args.iter().any(|a| a == "--allow-unknown");
+    if args.iter().any(|a| a == "--sim") {
+        use_simulator();
+    }
+    let args: Vec<&str> = args.iter().map(|a| a.as_str()).filter(|a| !["--json", "--sim", "--direct", "--allow-unknown"].contains(a)).collect();
+    if args.is_empty() || args == ["help"] || args == ["--help"] {
+        println!("{}", USAGE);
+        return;
+    }
+    let client = if direct { None } else { IpcClient::connect().ok() };
+    let result = match client {
+        Some(mut client) => client.check_version().and_then(|_| run_remote(&mut client, &args)),
+        None => {
+            ec_init();
+            // 未识别的机型只允许查看机型信息
+            match device_init(ec_open().as_ref(), allow_unknown) {
+                Err(e) if args != ["device"] => Err(e),
+                _ => run(&args, json_out),
+            }
+        }
+    };
+    match result {
+        Ok(value) if json_out => println!("{}", value),
+        Ok(value) => print_text(&value),
+        Err(e) => {
+            if json_out {
+                println!("{}", json!({ "error": e }));
+            } else {
+                eprintln!("{}", e);
+            }
+            process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use std::{env, process};
+use x15_71fan::modules::{
+    device::device_init,
+    ec::{ec_init, ec_open, is_simulated, use_simulator},
+    fan::fan_reset,
+    profile::load_profile,
+    config::load_daemon_config,
+    control::{restore_settings, Controller},
+    ipc::{start_services, stop_server},
+};
+
+const USAGE: &str = "用法: nuc-fan-daemon [--sim] [--verbose] [--allow-unknown] [--profile <name>]
+
+  --sim             使用模拟 EC(非 Windows 平台默认启用)
+  --allow-unknown   在未识别的机型上也写 EC
+  --verbose         每次采样输出一行 JSON 快照
+  --profile <name>  启动时运行的风扇配置，默认读取 daemon_config.json";
+
+struct Args {
+    sim: bool,
+    verbose: bool,
+    allow_unknown: bool,
+    profile: Option<String>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args { sim: false, verbose: false, allow_unknown: false, profile: None };
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--sim" => args.sim = true,
+            "--verbose" => args.verbose = true,
+            "--allow-unknown" => args.allow_unknown = true,
+            "--profile" => args.profile = Some(iter.next().ok_or("--profile 缺少配置名")?),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    Ok(args)
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(2);
+    });
+    if args.sim {
+        use_simulator();
+    }
+    ec_init();
+    println!("nuc-fan-daemon 启动{}", if is_simulated() { "(模拟 EC)" } else { "" });
+    if let Err(e) = device_init(ec_open().as_ref(), args.allow_unknown) {
+        eprintln!("{}", e);
+        process::exit(1);
+    }
+    // 先恢复保存的设置，其中的 fan_reset 不能晚于下面启动配置时的 fan_init
+    restore_settings(ec_open().as_ref());
+
+    let controller = Controller::new();
+    if let Err(e) = start_services(&controller) {
+        eprintln!("服务启动失败: {}", e);
+        process::exit(1);
+    }
+    let verbose = args.verbose;
+    controller.spawn(move |snapshot| {
+        if verbose {
+            println!("{}", serde_json::to_string(snapshot).unwrap_or_default());
+        }
+    });
+
+    let profile = args.profile.or(load_daemon_config().profile);
+    if let Some(name) = profile {
+        match load_profile(&name).and_then(|d| serde_json::to_value(d).map_err(|e| e.to_string())) {
+            Ok(fan_data) => {
+                controller.start(ec_open().as_ref(), &name, fan_data);
+                println!("已运行风扇配置 {}", name);
+            }
+            Err(e) => eprintln!("风扇配置 {} 加载失败: {}", name, e),
+        }
+    }
+
+    // 退出前恢复 EC 自动控制
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    rt.block_on(shutdown_signal());
+    controller.stop();
+    fan_reset(ec_open().as_ref());
+    stop_server();
+    println!("nuc-fan-daemon 已退出");
+}
+
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut term = signal(SignalKind::terminate()).expect("注册 SIGTERM 失败");
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {},
+            _ = term.recv() => {},
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
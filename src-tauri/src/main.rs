@@ -3,233 +3,384 @@
     windows_subsystem = "windows"
 )]
 use std::{
-    fs,
     env,
     thread,
     process,
     time::Duration,
-    sync::{Arc, Mutex, mpsc},
 };
-use powershell_script;
 use powershell_script::PsScriptBuilder;
 // use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 use notify_rust::Notification;
-use tauri::{self, State};
-use windows::Win32::System::Wmi::IWbemClassObject;
+use tauri::{self, Emitter};
 
-mod modules;
-use modules::{
+use x15_71fan::modules::{
     setup,
-    wmi::{wmi_security, wmi_init, wmi_set},
-    config::{get_config_dir, get_config_file_path},
-    struct_set::{FanData, FanSpeeds, Tdp, FanControlState, ChannelControlState},
+    ec::WriteReport,
+    profile::DEFAULT_PROFILE,
+    battery::Battery,
+    power::PowerStatus,
+    rgb::{KeyboardRgb, RgbTarget},
+    device::Device,
+    explorer::{RegisterDiff, RegisterSnapshot, RegisterValue},
+    vendor_fan::{FanTableFit, VendorFan},
+    ipc::{ensure_server, rpc_call, IpcClient},
+    struct_set::{
+        ChargeSchedule, FanData, FanSpeeds, Tdp, PollConfig, PowerConfig,
+        PowerLimits, ModeDefaults, NvOc, Rgb, RgbPreset, LightbarState, ThermalIndicator,
+        VendorFanSettings,
+        TelemetryConfig, TelemetryPoint, SensorSnapshot,
+    },
 };
 
-struct WmiState {
-    pub wmi: Arc<Mutex<IWbemClassObject>>,
+// 界面命令都转发到 IPC 服务(守护进程或本进程内置服务)
+fn call<T: DeserializeOwned>(method: &str, params: Value) -> Result<T, String> {
+    let result = rpc_call(method, params)?;
+    serde_json::from_value(result).map_err(|e| e.to_string())
 }
 
 #[tauri::command]
 async fn save_fan_config(fan_data: FanData) -> Result<(), String> {
-    // 获取配置文件路径
-    let config_path = get_config_file_path()?;
+    call::<Value>("profile.save", json!({ "name": DEFAULT_PROFILE, "fan_data": fan_data }))?;
+    println!("风扇配置已保存");
+    Ok(())
+}
 
-    // 将 fan_data 序列化为 JSON
-    let json_data = serde_json::to_string_pretty(&fan_data).map_err(|e| e.to_string())?;
+#[tauri::command]
+async fn load_fan_config() -> Result<FanData, String> {
+    let fan_data = call("profile.get", json!({ "name": DEFAULT_PROFILE }))?;
+    println!("风扇配置已加载");
+    Ok(fan_data)
+}
 
-    // 写入配置文件
-    fs::write(config_path, json_data).map_err(|e| e.to_string())?;
+#[tauri::command]
+async fn list_fan_profiles() -> Result<Vec<String>, String> {
+    call("profile.list", Value::Null)
+}
 
-    println!("风扇配置已保存");
+#[tauri::command]
+async fn load_fan_profile(name: String) -> Result<FanData, String> {
+    call("profile.get", json!({ "name": name }))
+}
+
+#[tauri::command]
+async fn save_fan_profile(name: String, fan_data: FanData) -> Result<(), String> {
+    call::<Value>("profile.save", json!({ "name": name, "fan_data": fan_data }))?;
     Ok(())
 }
+
 #[tauri::command]
-async fn load_fan_config() -> FanData {
-    // 获取配置文件路径
-    let config_path = get_config_file_path().unwrap();
-    // 检查配置文件是否存在
-    if !config_path.exists() {
-        println!("配置文件不存在");
-    }
-    // 读取配置文件
-    let json_data = fs::read_to_string(config_path).map_err(|e| e.to_string()).unwrap();
-    // 反序列化为 FanData
-    let fan_data: FanData = serde_json::from_str(&json_data).map_err(|e| e.to_string()).unwrap();
-    println!("风扇配置已加载");
-    fan_data
+async fn get_device() -> Result<Option<Device>, String> {
+    call("device.get", Value::Null)
+}
+
+#[tauri::command]
+async fn get_vendor_fan() -> Result<VendorFan, String> {
+    call("vendor_fan.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_vendor_fan(settings: VendorFanSettings) -> Result<(), String> {
+    call::<Value>("vendor_fan.set", json!({ "settings": settings }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn fit_fan_table(name: String, mode: u8) -> Result<FanTableFit, String> {
+    call("vendor_fan.fit", json!({ "name": name, "mode": mode }))
 }
 
 #[tauri::command]
-async fn get_tdp() -> (i64, i64, i64, i64, i64) {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let gpu1 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000073d") & 0xFF;
-    let gpu2 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000733") & 0xFF;
-    let cpu1 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000783") & 0xFF;
-    let cpu2 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000784");
-    let tcc = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000786");
-    (cpu1, cpu2, gpu1, gpu2, tcc)
+async fn program_fan_table(name: String, mode: u8) -> Result<FanTableFit, String> {
+    call("vendor_fan.program", json!({ "name": name, "mode": mode }))
 }
 
 #[tauri::command]
-async fn set_tdp(t: Tdp) {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let _gpu1 = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}073d", t.gpu1).as_str());
-    let _gpu2 = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}0733", t.gpu2).as_str());
-    let _cpu1 = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}0783", t.cpu1).as_str());
-    let _cpu2 = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}0784", t.cpu2).as_str());
-    let _tcc = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}0786", t.tcc).as_str());
+async fn disable_vendor_fan() -> Result<(), String> {
+    call::<Value>("vendor_fan.disable", Value::Null)?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_tdp() -> Result<Tdp, String> {
+    call("tdp.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_tdp(t: Tdp) -> Result<(), String> {
+    // 全部寄存器读回一致才提示成功，否则把失败的寄存器返回给界面
+    call::<WriteReport>("tdp.set", json!({ "tdp": t }))?.check()?;
     Notification::new()
         .summary("NUC X15 Fan Control")
         .body("TDP设置成功")
         .icon("firefox")
         .show()
         .unwrap();
+    Ok(())
 }
 
 #[tauri::command]
-async fn get_fan_speeds() -> FanSpeeds {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let l_fan_1 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000046C");
-    let l_fan_2 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000046D");
-    let r_fan_1 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000464");
-    let r_fan_2 = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000465");
-    let l_temp = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000043E");
-    let r_temp = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000044F");
+async fn get_power_limits() -> Result<PowerLimits, String> {
+    call("limits.get", Value::Null)
+}
 
-    FanSpeeds {
-        left_fan_speed: (l_fan_1 & 0xFF) << 8 | l_fan_2,
-        right_fan_speed: (r_fan_1 & 0xFF) << 8 | r_fan_2,
-        left_temp: l_temp,
-        right_temp: r_temp & 0xFF
-    }
+#[tauri::command]
+async fn set_power_limits(limits: PowerLimits) -> Result<(), String> {
+    call::<WriteReport>("limits.set", json!({ "limits": limits }))?.check()
 }
+
 #[tauri::command]
-fn start_fan_control(fan_data: serde_json::Value, state: State<FanControlState>, tx: State<ChannelControlState>) {
-    Arc::clone(&tx.tx).lock().unwrap().send("0x000001000000044F".to_string()).unwrap();
-    let is_running = Arc::clone(&state.is_running);
-    Notification::new()
-        .summary("NUC X15 Fan Control")
-        .body("正在运行")
-        .icon("firefox")
-        .show()
-        .unwrap();
-    // 打印接收到的风扇数据
-    // println!("left fan data: {:?}", fan_data.get("left_fan"));
-    // println!("right fan data: {:?}", fan_data.get("right_fan"));
-    println!("接受风扇配置信息");
-    fan_init();
-    // 如果已经在运行，跳过启动
-    if *is_running.lock().unwrap() {
-        println!("Fan control is already running.");
-        return;
-    }
-    // 启动新的控制线程
-    *is_running.lock().unwrap() = true;
-    thread::spawn(move || {
-        while *is_running.lock().unwrap() {
-            // 模拟执行时间
-            // println!("Fan control loop running...");
-            cpu_temp(&fan_data.get("left_fan"), &fan_data.get("right_fan"));
-            thread::sleep(Duration::from_secs(1));
-            // println!("TEMP: {}", cpu_temp());
-        }
-        println!("Fan control stopped.");
-    });
-}
-
-#[tauri::command]
-fn stop_fan_control(state: State<FanControlState>) {
-    let mut is_running = state.is_running.lock().unwrap();
-    // fan_init();
-    *is_running = false; // 停止风扇控制
-    thread::spawn(move || {
-        thread::sleep(Duration::from_secs(1));
-        fan_reset();
-        Notification::new()
-            .summary("NUC X15 Fan Control")
-            .body("停止运行")
-            .icon("firefox")
-            .show()
-            .unwrap();
-    });
+async fn get_nv_oc() -> Result<NvOc, String> {
+    call("nv_oc.get", Value::Null)
 }
 
-fn speed_c(speed_n: i64, speed_l: i64, temp_n: i64, temp_l: i64, temp: i64) -> i64 {
-    println!("{} {} {} {} {}", speed_n, speed_l, temp_n, temp_l, temp);
-    speed_l + (((speed_n - speed_l) as f64 / ((temp_n - temp_l) as f64 + 0.001)) * (temp - temp_l) as f64) as i64
+#[tauri::command]
+async fn set_nv_oc(config: NvOc) -> Result<(), String> {
+    call::<WriteReport>("nv_oc.set", json!({ "config": config }))?.check()
 }
 
-fn cpu_temp(left: &Option<&serde_json::Value>, right: &Option<&serde_json::Value>) {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let cpu_out = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000043E".to_string().as_str());
-    let gpu_out = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x000001000000044F".to_string().as_str()) & 0xFF;
-    println!("CPU Temp: {:?}, GPU Temp: {:?}", &cpu_out, &gpu_out);
-    if cpu_out > 95 || gpu_out > 95 {
-        fan_set(100, 100);
-        return;
-    }
-    let (mut l_c, mut s_c, mut r_c, mut s_c_) = (0i64, 0i64, 0i64, 0i64);
-    if let (Some(left), Some(right)) = (left.expect("l").as_array(), right.expect("r").as_array()) {
-        for l_ in left {
-            if let (Some(l), Some(s)) = (
-                l_.get("temperature").expect("转换错误").as_i64(), l_.get("speed").expect("转换错误").as_i64()
-            ) {
-                if l >= cpu_out {
-                    for r_ in right {
-                        if let (Some(r), Some(s_)) = (
-                            r_.get("temperature").expect("转换错误").as_i64(), r_.get("speed").expect("转换错误").as_i64()
-                        ) {
-                            if r >= gpu_out {
-                                let s = speed_c(s, s_c, l, l_c, cpu_out);
-                                let s_ = speed_c(s_, s_c_, r, r_c, gpu_out);
-                                fan_set(
-                                    s as i16,
-                                    s_ as i16,
-                                );
-                                println!("cpu_t: {:?} l_fan: {:?} gpu_t: {:?} r_fan: {:?}", cpu_out, s, gpu_out, s_);
-                                return;
-                            } else {
-                                (l_c, s_c, r_c, s_c_) = (l, s, r, s_);
-                            }
-                        }
-                    }
-                }
-            }
-        }
-    }
+#[tauri::command]
+async fn get_mode_defaults() -> Result<Vec<ModeDefaults>, String> {
+    call("limits.defaults", Value::Null)
 }
 
-fn fan_init() {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let out = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000751".to_string().as_str());
-    if out - 27728 == -64 {
-        let _ = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000000000400751".to_string().as_str());
-    }
+#[tauri::command]
+async fn restore_mode_defaults(mode: u8) -> Result<PowerLimits, String> {
+    call("limits.restore", json!({ "mode": mode }))
 }
 
-fn fan_reset() {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let _ = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000000000A00751".to_string().as_str());
+#[tauri::command]
+async fn list_power_profiles() -> Result<Vec<String>, String> {
+    call("power_profile.list", Value::Null)
 }
 
-fn fan_set(left: i16, right: i16) {
-    let (in_cls, svc, obj_path, method_name) = wmi_init();
-    let out = wmi_set(&in_cls, &svc, &obj_path, &method_name, "0x0000010000000751".to_string().as_str());
-    if out - 27728 == -64 && out - 27728 == -80{
-        fan_init();
-        // println!("风扇状态异常已尝试恢复");
-        Notification::new()
-            .summary("NUC X15 Fan Control")
-            .body("风扇状态异常已尝试恢复")
-            .icon("firefox")
-            .show()
-            .unwrap();
-    }
-    let left = left * 2;
-    let right = right * 2;
-    println!("{} {}", left, right);
-    let _ = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}1809", left).as_str());
-    let _ = wmi_set(&in_cls, &svc, &obj_path, &method_name, format!("0x000000000{:02x}1804", right).as_str());
+#[tauri::command]
+async fn save_power_profile(name: String, limits: Option<PowerLimits>) -> Result<(), String> {
+    call::<Value>("power_profile.save", json!({ "name": name, "limits": limits }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn apply_power_profile(name: String) -> Result<PowerLimits, String> {
+    call::<WriteReport>("power_profile.apply", json!({ "name": name }))?.check()?;
+    call("limits.get", Value::Null)
+}
+
+#[tauri::command]
+async fn get_keyboard_rgb() -> Result<KeyboardRgb, String> {
+    call("rgb.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_keyboard_rgb(target: RgbTarget, color: Rgb) -> Result<(), String> {
+    call::<Value>("rgb.set", json!({ "target": target, "color": color }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn step_keyboard_brightness(direction: String) -> Result<(), String> {
+    call::<Value>("rgb.brightness", json!({ "direction": direction }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn restore_keyboard_rgb() -> Result<Rgb, String> {
+    call("rgb.restore_default", Value::Null)
+}
+
+#[tauri::command]
+async fn list_rgb_presets() -> Result<Vec<String>, String> {
+    call("rgb.preset.list", Value::Null)
+}
+
+#[tauri::command]
+async fn save_rgb_preset(name: String, preset: Option<RgbPreset>) -> Result<(), String> {
+    call::<Value>("rgb.preset.save", json!({ "name": name, "preset": preset }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn apply_rgb_preset(name: String) -> Result<RgbPreset, String> {
+    call("rgb.preset.apply", json!({ "name": name }))
+}
+
+#[tauri::command]
+async fn delete_rgb_preset(name: String) -> Result<(), String> {
+    call::<Value>("rgb.preset.delete", json!({ "name": name }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_lightbar() -> Result<LightbarState, String> {
+    call("lightbar.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_lightbar(on: bool, color: Option<Rgb>) -> Result<(), String> {
+    call::<Value>("lightbar.set", json!({ "on": on, "color": color }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_thermal_indicator() -> Result<ThermalIndicator, String> {
+    call("lightbar.thermal.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_thermal_indicator(config: ThermalIndicator) -> Result<(), String> {
+    call::<Value>("lightbar.thermal.set", json!({ "config": config }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_fan_speeds() -> Result<FanSpeeds, String> {
+    // 返回控制循环最近一次采样，不再单独读取硬件
+    let status: Value = call("status", Value::Null)?;
+    let snapshot: Option<SensorSnapshot> = serde_json::from_value(status["snapshot"].clone()).map_err(|e| e.to_string())?;
+    Ok(match snapshot {
+        Some(snapshot) => FanSpeeds {
+            left_fan_speed: snapshot.sample.left_fan_speed,
+            right_fan_speed: snapshot.sample.right_fan_speed,
+            left_temp: snapshot.sample.cpu_temp,
+            right_temp: snapshot.sample.gpu_temp,
+        },
+        None => FanSpeeds { left_fan_speed: 0, right_fan_speed: 0, left_temp: 0, right_temp: 0 },
+    })
+}
+
+#[tauri::command]
+async fn get_battery() -> Result<Battery, String> {
+    call("battery.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_charge_limit(limit: u8) -> Result<(), String> {
+    call::<Value>("battery.set_charge_limit", json!({ "limit": limit }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_charge_schedule() -> Result<ChargeSchedule, String> {
+    call("battery.get_charge_schedule", Value::Null)
+}
+
+#[tauri::command]
+async fn set_charge_schedule(schedule: ChargeSchedule) -> Result<(), String> {
+    call::<Value>("battery.set_charge_schedule", json!({ "schedule": schedule }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_power_status() -> Result<PowerStatus, String> {
+    call("power.get", Value::Null)
+}
+
+#[tauri::command]
+async fn get_power_config() -> Result<PowerConfig, String> {
+    call("power.config.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_power_config(config: PowerConfig) -> Result<(), String> {
+    call::<Value>("power.config.set", json!({ "config": config }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_poll_config() -> Result<PollConfig, String> {
+    call("poll.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_poll_config(config: PollConfig) -> Result<(), String> {
+    call::<Value>("poll.set", json!({ "config": config }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_telemetry_config() -> Result<TelemetryConfig, String> {
+    call("telemetry.config.get", Value::Null)
+}
+
+#[tauri::command]
+async fn set_telemetry_config(config: TelemetryConfig) -> Result<(), String> {
+    call::<Value>("telemetry.config.set", json!({ "config": config }))?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_telemetry(start: Option<u64>, end: Option<u64>, resolution_ms: Option<u64>) -> Result<Vec<TelemetryPoint>, String> {
+    call("telemetry.query", json!({ "start": start, "end": end, "resolution_ms": resolution_ms }))
+}
+
+#[tauri::command]
+async fn export_telemetry(
+    start: u64,
+    end: Option<u64>,
+    format: String,
+    columns: Option<Vec<String>>,
+    path: Option<String>,
+) -> Result<String, String> {
+    let params = json!({ "start": start, "end": end, "format": format, "columns": columns, "path": path });
+    let result: Value = call("telemetry.export", params)?;
+    Ok(result["path"].as_str().unwrap_or_default().to_string())
+}
+
+#[tauri::command]
+async fn dump_ec_registers(start: u16, len: u32) -> Result<Vec<RegisterValue>, String> {
+    call("ec.dump", json!({ "start": start, "len": len }))
+}
+
+#[tauri::command]
+async fn take_ec_snapshot(name: String) -> Result<RegisterSnapshot, String> {
+    call("ec.snapshot.take", json!({ "name": name }))
+}
+
+#[tauri::command]
+async fn list_ec_snapshots() -> Result<Vec<String>, String> {
+    call("ec.snapshot.list", Value::Null)
+}
+
+#[tauri::command]
+async fn diff_ec_snapshots(from: String, to: Option<String>) -> Result<Vec<RegisterDiff>, String> {
+    call("ec.snapshot.diff", json!({ "from": from, "to": to }))
+}
+
+#[tauri::command]
+async fn export_ec_snapshot(name: String, path: Option<String>) -> Result<String, String> {
+    let result: Value = call("ec.snapshot.export", json!({ "name": name, "path": path }))?;
+    Ok(result["path"].as_str().unwrap_or_default().to_string())
+}
+
+#[tauri::command]
+async fn import_ec_snapshot(path: String, name: Option<String>) -> Result<RegisterSnapshot, String> {
+    call("ec.snapshot.import", json!({ "path": path, "name": name }))
+}
+
+#[tauri::command]
+async fn start_fan_control(fan_data: FanData) -> Result<(), String> {
+    call::<Value>("profile.apply", json!({ "name": "custom", "fan_data": fan_data }))?;
+    Notification::new()
+        .summary("NUC X15 Fan Control")
+        .body("正在运行")
+        .icon("firefox")
+        .show()
+        .unwrap();
+    Ok(())
+}
+
+#[tauri::command]
+async fn stop_fan_control() -> Result<(), String> {
+    // 服务端停止调速后立即恢复 EC 自动控制
+    call::<Value>("profile.stop", Value::Null)?;
+    Notification::new()
+        .summary("NUC X15 Fan Control")
+        .body("停止运行")
+        .icon("firefox")
+        .show()
+        .unwrap();
+    Ok(())
 }
 
 fn main() {
@@ -252,37 +403,98 @@ fn main() {
              process::exit(0);
          }
     };
-    let (tx, rx) = mpsc::channel::<String>();
-    let (tx1, _rx1) = mpsc::channel::<i64>();
-    thread::spawn(move || {
-        wmi_security();
-        let (in_cls, svc, obj_path, method_name) = wmi_init();
-        while let Ok(data) = rx.recv() {
-            let out = wmi_set(&in_cls, &svc, &obj_path, &method_name, data.as_str());
-            println!("{:?}", out);
-        }
-    });
-    let channel_control_state = ChannelControlState {
-        tx: Arc::new(Mutex::new(tx)),
-    };
-    let fan_control_state = FanControlState {
-        is_running: Arc::new(Mutex::new(false)),
-    };
+    // 优先连接 nuc-fan-daemon，没有时在本进程内启动服务
+    if let Err(e) = ensure_server().and_then(|_| IpcClient::connect()?.check_version()) {
+        Notification::new()
+            .summary("NUC X15 Fan Control")
+            .body(&e)
+            .icon("firefox")
+            .show()
+            .unwrap();
+        process::exit(1);
+    }
     tauri::Builder::default()
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_notification::init())
-        .setup(|app| setup::init(app))
-        .manage(fan_control_state)
-        .manage(channel_control_state)
+        .setup(|app| {
+            let handle = app.handle().clone();
+            thread::spawn(move || loop {
+                let result = IpcClient::connect().and_then(|client| {
+                    client.subscribe(&["snapshot"], |_, snapshot| {
+                        if let Err(e) = handle.emit("sensor-snapshot", snapshot) {
+                            println!("推送传感器数据失败: {}", e);
+                        }
+                    })
+                });
+                if let Err(e) = result {
+                    println!("传感器订阅断开: {}", e);
+                }
+                // 守护进程退出后改用内置服务
+                if let Err(e) = ensure_server() {
+                    println!("{}", e);
+                }
+                thread::sleep(Duration::from_secs(2));
+            });
+            setup::init(app)
+        })
         .invoke_handler(tauri::generate_handler![
             start_fan_control,
             stop_fan_control,
             save_fan_config,
             load_fan_config,
+            list_fan_profiles,
+            load_fan_profile,
+            save_fan_profile,
             get_fan_speeds,
+            get_battery,
+            set_charge_limit,
+            get_charge_schedule,
+            set_charge_schedule,
+            get_power_status,
+            get_power_config,
+            set_power_config,
+            get_device,
+            get_vendor_fan,
+            set_vendor_fan,
+            fit_fan_table,
+            program_fan_table,
+            disable_vendor_fan,
             get_tdp,
             set_tdp,
+            get_power_limits,
+            set_power_limits,
+            get_nv_oc,
+            set_nv_oc,
+            get_mode_defaults,
+            restore_mode_defaults,
+            list_power_profiles,
+            save_power_profile,
+            apply_power_profile,
+            get_keyboard_rgb,
+            set_keyboard_rgb,
+            step_keyboard_brightness,
+            restore_keyboard_rgb,
+            list_rgb_presets,
+            save_rgb_preset,
+            apply_rgb_preset,
+            delete_rgb_preset,
+            get_lightbar,
+            set_lightbar,
+            get_thermal_indicator,
+            set_thermal_indicator,
+            get_poll_config,
+            set_poll_config,
+            get_telemetry_config,
+            set_telemetry_config,
+            get_telemetry,
+            export_telemetry,
+            dump_ec_registers,
+            take_ec_snapshot,
+            list_ec_snapshots,
+            diff_ec_snapshots,
+            export_ec_snapshot,
+            import_ec_snapshot,
         ])
         .on_window_event(|window, event|
             if let tauri::WindowEvent::CloseRequested { api, .. } = event {
@@ -0,0 +1,134 @@
+use serde::{Deserialize, Serialize};
+use crate::modules::config::{load_battery_config, save_battery_config};
+use crate::modules::ec::{ec_read, ec_read_u16, ec_write_verified, Ec, WriteReport};
+
+const ADDR_BAT_POWER_UNIT: u16 = 1024;
+const ADDR_BAT_DESIGN_CAPACITY: u16 = 1026;
+const ADDR_BAT_LAST_FULL_CHARGE_CAPACITY: u16 = 1028;
+const ADDR_BAT_DESIGN_VOL: u16 = 1032;
+const ADDR_BAT_STATE: u16 = 1074;
+const ADDR_BAT_CURRENT: u16 = 1076;
+const ADDR_BAT_REMAINING_CAPACITY: u16 = 1078;
+const ADDR_BAT_VOLTAGE: u16 = 1080;
+const ADDR_EC_BT1_CYCLE_COUNT: u16 = 1190;
+const ADDR_CHARGING_LIMIT: u16 = 1977;
+
+// EC 接受的充电上限(%)，100 为不限制
+pub const CHARGE_LIMITS: [u8; 6] = [50, 60, 70, 80, 90, 100];
+
+// 状态位与 ACPI _BST 一致
+const STATE_DISCHARGING: u16 = 0x01;
+const STATE_CHARGING: u16 = 0x02;
+const STATE_CRITICAL: u16 = 0x04;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum BatteryState {
+    Charging,
+    Discharging,
+    // 接着电源但没有充放电，通常是已充满或达到充电上限
+    #[default]
+    Idle,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct Battery {
+    pub design_capacity_mwh: u32,
+    pub full_charge_capacity_mwh: u32,
+    pub remaining_capacity_mwh: u32,
+    pub design_voltage_mv: u32,
+    pub voltage_mv: u32,
+    // 放电时为负
+    pub current_ma: i32,
+    pub cycle_count: u32,
+    // 剩余电量(%)
+    pub percent: f64,
+    // 满充容量 / 设计容量(%)
+    pub health: f64,
+    pub state: BatteryState,
+    pub critical: bool,
+    // EC 当前的充电上限(%)
+    pub charge_limit: u8,
+}
+
+fn state(raw: u16) -> BatteryState {
+    if raw & STATE_CHARGING != 0 {
+        BatteryState::Charging
+    } else if raw & STATE_DISCHARGING != 0 {
+        BatteryState::Discharging
+    } else {
+        BatteryState::Idle
+    }
+}
+
+fn ratio(part: u32, whole: u32) -> f64 {
+    if whole == 0 {
+        return 0.0;
+    }
+    (part as f64 * 100.0 / whole as f64).min(100.0)
+}
+
+// 容量单位与 ACPI _BIF 一致: 0 为 mWh，1 为 mAh，按设计电压换算成 mWh
+pub fn read_battery(ec: &dyn Ec) -> Battery {
+    let design_voltage_mv = ec_read_u16(ec, ADDR_BAT_DESIGN_VOL) as u32;
+    let power_unit = ec_read(ec, ADDR_BAT_POWER_UNIT);
+    let to_mwh = |raw: u16| match power_unit {
+        1 => raw as u32 * design_voltage_mv / 1000,
+        _ => raw as u32,
+    };
+    let design_capacity_mwh = to_mwh(ec_read_u16(ec, ADDR_BAT_DESIGN_CAPACITY));
+    let full_charge_capacity_mwh = to_mwh(ec_read_u16(ec, ADDR_BAT_LAST_FULL_CHARGE_CAPACITY));
+    let remaining_capacity_mwh = to_mwh(ec_read_u16(ec, ADDR_BAT_REMAINING_CAPACITY));
+    let raw_state = ec_read_u16(ec, ADDR_BAT_STATE);
+    Battery {
+        design_capacity_mwh,
+        full_charge_capacity_mwh,
+        remaining_capacity_mwh,
+        design_voltage_mv,
+        voltage_mv: ec_read_u16(ec, ADDR_BAT_VOLTAGE) as u32,
+        current_ma: ec_read_u16(ec, ADDR_BAT_CURRENT) as i16 as i32,
+        cycle_count: ec_read_u16(ec, ADDR_EC_BT1_CYCLE_COUNT) as u32,
+        percent: ratio(remaining_capacity_mwh, full_charge_capacity_mwh),
+        health: ratio(full_charge_capacity_mwh, design_capacity_mwh),
+        state: state(raw_state),
+        critical: raw_state & STATE_CRITICAL != 0,
+        charge_limit: read_charge_limit(ec),
+    }
+}
+
+pub fn check_charge_limit(limit: u8) -> Result<(), String> {
+    if !CHARGE_LIMITS.contains(&limit) {
+        return Err(format!("充电上限只能是 {:?} 之一: {}", CHARGE_LIMITS, limit));
+    }
+    Ok(())
+}
+
+// 未设置过时 EC 读出 0，视为不限制
+pub fn read_charge_limit(ec: &dyn Ec) -> u8 {
+    match ec_read(ec, ADDR_CHARGING_LIMIT) as u8 {
+        0 => 100,
+        limit => limit,
+    }
+}
+
+// 写入后读回确认；成功才记入 battery_config.json，EC 掉电后由 apply_battery_config 恢复
+pub fn set_charge_limit(ec: &dyn Ec, limit: u8) -> Result<(), String> {
+    check_charge_limit(limit)?;
+    WriteReport::new(vec![ec_write_verified(ec, ADDR_CHARGING_LIMIT, limit)]).check()?;
+    let mut config = load_battery_config();
+    config.charge_limit = Some(limit);
+    save_battery_config(&config)
+}
+
+// 启动时恢复保存的电池设置
+pub fn apply_battery_config(ec: &dyn Ec) {
+    let config = load_battery_config();
+    if let Some(limit) = config.charge_limit {
+        let restored = check_charge_limit(limit)
+            .and_then(|_| WriteReport::new(vec![ec_write_verified(ec, ADDR_CHARGING_LIMIT, limit)]).check());
+        match restored {
+            Ok(()) => println!("已恢复充电上限 {}%", limit),
+            Err(e) => println!("battery_config.json: {}", e),
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use crate::modules::config::{load_battery_config, save_battery_config};
+use crate::modules::ec::{ec_read, ec_write_all, Ec};
+use crate::modules::struct_set::ChargeSchedule;
+
+const ADDR_SECOND_CHARGE_RATE_START_TIME_H: u16 = 2012;
+const ADDR_SECOND_CHARGE_RATE_START_TIME_L: u16 = 2013;
+const ADDR_SECOND_CHARGE_RATE_END_TIME_H: u16 = 2014;
+const ADDR_SECOND_CHARGE_RATE_END_TIME_L: u16 = 2015;
+const ADDR_SECOND_CHARGE_RATE: u16 = 2016;
+const ADDR_FIRST_CHARGE_RATE: u16 = 2017;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+// 时间按当天分钟数存放，H 为高字节，L 为低字节。
+// 分析.md 只给出了 _H/_L 寄存器名，没有说明编码，这是推测而非文档行为
+pub fn encode_time(minutes: u16) -> Result<(u8, u8), String> {
+    if minutes >= MINUTES_PER_DAY {
+        return Err(format!("时间超出范围 0~{}: {}", MINUTES_PER_DAY - 1, minutes));
+    }
+    Ok(((minutes >> 8) as u8, (minutes & 0xFF) as u8))
+}
+
+pub fn decode_time(h: u8, l: u8) -> Result<u16, String> {
+    let minutes = ((h as u16) << 8) | l as u16;
+    if minutes >= MINUTES_PER_DAY {
+        return Err(format!("EC 中的时间超出范围 0~{}: {}", MINUTES_PER_DAY - 1, minutes));
+    }
+    Ok(minutes)
+}
+
+// "HH:MM" -> 当天分钟数
+pub fn parse_hhmm(s: &str) -> Result<u16, String> {
+    let invalid = || format!("无效的时间(应为 HH:MM): {}", s);
+    let (h, m) = s.trim().split_once(':').ok_or_else(invalid)?;
+    let (h, m) = (h.parse::<u16>().map_err(|_| invalid())?, m.parse::<u16>().map_err(|_| invalid())?);
+    if h > 23 || m > 59 {
+        return Err(invalid());
+    }
+    Ok(h * 60 + m)
+}
+
+pub fn format_hhmm(minutes: u16) -> String {
+    format!("{:02}:{:02}", minutes / 60 % 24, minutes % 60)
+}
+
+// 充电速率为最大充电电流的百分比；起止时间相同表示不启用第二速率，结束早于开始表示跨午夜
+pub fn check_charge_schedule(schedule: &ChargeSchedule) -> Result<(), String> {
+    for (name, rate) in [("first_rate", schedule.first_rate), ("second_rate", schedule.second_rate)] {
+        if !(1..=100).contains(&rate) {
+            return Err(format!("{} 超出范围 1~100: {}", name, rate));
+        }
+    }
+    parse_hhmm(&schedule.second_start)?;
+    parse_hhmm(&schedule.second_end)?;
+    Ok(())
+}
+
+pub fn read_charge_schedule(ec: &dyn Ec) -> ChargeSchedule {
+    let read_time = |h: u16, l: u16| {
+        let minutes = decode_time(ec_read(ec, h) as u8, ec_read(ec, l) as u8).unwrap_or_else(|e| {
+            println!("{}", e);
+            MINUTES_PER_DAY - 1
+        });
+        format_hhmm(minutes)
+    };
+    ChargeSchedule {
+        first_rate: ec_read(ec, ADDR_FIRST_CHARGE_RATE) as u8,
+        second_rate: ec_read(ec, ADDR_SECOND_CHARGE_RATE) as u8,
+        second_start: read_time(ADDR_SECOND_CHARGE_RATE_START_TIME_H, ADDR_SECOND_CHARGE_RATE_START_TIME_L),
+        second_end: read_time(ADDR_SECOND_CHARGE_RATE_END_TIME_H, ADDR_SECOND_CHARGE_RATE_END_TIME_L),
+    }
+}
+
+fn write_charge_schedule(ec: &dyn Ec, schedule: &ChargeSchedule) -> Result<(), String> {
+    check_charge_schedule(schedule)?;
+    let (start_h, start_l) = encode_time(parse_hhmm(&schedule.second_start)?)?;
+    let (end_h, end_l) = encode_time(parse_hhmm(&schedule.second_end)?)?;
+    ec_write_all(ec, &[
+        (ADDR_FIRST_CHARGE_RATE, schedule.first_rate),
+        (ADDR_SECOND_CHARGE_RATE, schedule.second_rate),
+        (ADDR_SECOND_CHARGE_RATE_START_TIME_H, start_h),
+        (ADDR_SECOND_CHARGE_RATE_START_TIME_L, start_l),
+        (ADDR_SECOND_CHARGE_RATE_END_TIME_H, end_h),
+        (ADDR_SECOND_CHARGE_RATE_END_TIME_L, end_l),
+    ])
+    .check()
+}
+
+// 六个寄存器全部写成功后才保存 charge_schedule，启动时由 apply_charge_schedule 重新写入
+pub fn set_charge_schedule(ec: &dyn Ec, schedule: &ChargeSchedule) -> Result<(), String> {
+    write_charge_schedule(ec, schedule)?;
+    let mut config = load_battery_config();
+    config.charge_schedule = Some(schedule.clone());
+    save_battery_config(&config)
+}
+
+pub fn apply_charge_schedule(ec: &dyn Ec) {
+    if let Some(schedule) = load_battery_config().charge_schedule {
+        match write_charge_schedule(ec, &schedule) {
+            Ok(()) => println!("已恢复充电速率 {}% / {}% ({}-{})", schedule.first_rate, schedule.second_rate, schedule.second_start, schedule.second_end),
+            Err(e) => println!("battery_config.json: {}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sim::{SimEc, SimState};
+
+    #[test]
+    fn time_round_trips_through_h_l() {
+        assert_eq!(encode_time(0), Ok((0, 0)));
+        assert_eq!(encode_time(1439), Ok((5, 159)));
+        for minutes in 0..MINUTES_PER_DAY {
+            let (h, l) = encode_time(minutes).unwrap();
+            assert_eq!(decode_time(h, l), Ok(minutes));
+        }
+    }
+
+    #[test]
+    fn time_outside_day_is_rejected() {
+        for minutes in [1440, 1441, u16::MAX] {
+            assert!(encode_time(minutes).is_err(), "{}", minutes);
+        }
+        assert!(decode_time(5, 160).is_err());
+        assert!(decode_time(0xFF, 0xFF).is_err());
+    }
+
+    #[test]
+    fn schedule_round_trips_through_ec() {
+        let ec = SimEc::new(SimState::new());
+        let schedule = ChargeSchedule {
+            first_rate: 100,
+            second_rate: 50,
+            second_start: "23:59".to_string(),
+            second_end: "00:00".to_string(),
+        };
+        write_charge_schedule(&ec, &schedule).unwrap();
+        assert_eq!(read_charge_schedule(&ec), schedule);
+    }
+}
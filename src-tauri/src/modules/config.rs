@@ -1,5 +1,24 @@
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::modules::struct_set::{
+    BatteryConfig, DaemonConfig, DeviceConfig, LightbarConfig, MetricsConfig, MqttConfig, PollConfig, PowerConfig, RestConfig, RgbConfig,
+    TelemetryConfig, VendorFanConfig,
+};
+
+const POLL_CONFIG: &str = "poll_config.json";
+const TELEMETRY_CONFIG: &str = "telemetry_config.json";
+const DAEMON_CONFIG: &str = "daemon_config.json";
+const METRICS_CONFIG: &str = "metrics_config.json";
+const MQTT_CONFIG: &str = "mqtt_config.json";
+const REST_CONFIG: &str = "rest_config.json";
+const BATTERY_CONFIG: &str = "battery_config.json";
+const POWER_CONFIG: &str = "power_config.json";
+const RGB_CONFIG: &str = "rgb_config.json";
+const LIGHTBAR_CONFIG: &str = "lightbar_config.json";
+const VENDOR_FAN_CONFIG: &str = "vendor_fan_config.json";
+const DEVICE_CONFIG: &str = "device_config.json";
 
 pub fn get_config_dir() -> PathBuf {
     dirs::config_dir().unwrap().join("com.nuc.x15.fan.cyear.app")
@@ -19,4 +38,156 @@ pub fn get_config_file_path() -> Result<PathBuf, String> {
     let config_file = config_dir.join("fan_config.json");
     println!("{:?}", &config_file);
     Ok(config_file)
-}
\ No newline at end of file
+}
+
+pub fn get_json_config_path(name: &str) -> Result<PathBuf, String> {
+    let config_dir = get_config_dir();
+    fs::create_dir_all(&config_dir).map_err(|e| e.to_string())?;
+    Ok(config_dir.join(name))
+}
+
+// 客户端给出的文件名只能落在 dir 之下，拒绝绝对路径和 ..
+pub fn resolve_in_dir(dir: &Path, name: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(name);
+    if name.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Err(format!("只允许 {:?} 下的相对路径: {}", dir, name));
+    }
+    let path = dir.join(relative);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    Ok(path)
+}
+
+pub fn load_json_config<T: DeserializeOwned + Default>(name: &str) -> T {
+    // 配置不存在或解析失败时使用默认值
+    get_json_config_path(name)
+        .and_then(|path| fs::read_to_string(path).map_err(|e| e.to_string()))
+        .and_then(|json_data| serde_json::from_str(&json_data).map_err(|e| e.to_string()))
+        .unwrap_or_default()
+}
+
+pub fn save_json_config<T: Serialize>(name: &str, config: &T) -> Result<(), String> {
+    let json_data = serde_json::to_string_pretty(config).map_err(|e| e.to_string())?;
+    fs::write(get_json_config_path(name)?, json_data).map_err(|e| e.to_string())
+}
+
+pub fn load_poll_config() -> PollConfig {
+    load_json_config(POLL_CONFIG)
+}
+
+pub fn save_poll_config(config: &PollConfig) -> Result<(), String> {
+    save_json_config(POLL_CONFIG, config)
+}
+
+pub fn load_telemetry_config() -> TelemetryConfig {
+    load_json_config(TELEMETRY_CONFIG)
+}
+
+pub fn save_telemetry_config(config: &TelemetryConfig) -> Result<(), String> {
+    save_json_config(TELEMETRY_CONFIG, config)
+}
+
+pub fn load_daemon_config() -> DaemonConfig {
+    load_json_config(DAEMON_CONFIG)
+}
+
+pub fn save_daemon_config(config: &DaemonConfig) -> Result<(), String> {
+    save_json_config(DAEMON_CONFIG, config)
+}
+
+pub fn load_metrics_config() -> MetricsConfig {
+    load_json_config(METRICS_CONFIG)
+}
+
+pub fn save_metrics_config(config: &MetricsConfig) -> Result<(), String> {
+    save_json_config(METRICS_CONFIG, config)
+}
+
+pub fn load_mqtt_config() -> MqttConfig {
+    load_json_config(MQTT_CONFIG)
+}
+
+pub fn save_mqtt_config(config: &MqttConfig) -> Result<(), String> {
+    save_json_config(MQTT_CONFIG, config)
+}
+
+pub fn load_rest_config() -> RestConfig {
+    load_json_config(REST_CONFIG)
+}
+
+// 含访问令牌，Unix 上只允许本用户读写
+pub fn save_rest_config(config: &RestConfig) -> Result<(), String> {
+    save_json_config(REST_CONFIG, config)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let path = get_json_config_path(REST_CONFIG)?;
+        fs::set_permissions(path, fs::Permissions::from_mode(0o600)).map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+pub fn load_battery_config() -> BatteryConfig {
+    load_json_config(BATTERY_CONFIG)
+}
+
+pub fn save_battery_config(config: &BatteryConfig) -> Result<(), String> {
+    save_json_config(BATTERY_CONFIG, config)
+}
+
+pub fn load_power_config() -> PowerConfig {
+    load_json_config(POWER_CONFIG)
+}
+
+pub fn save_power_config(config: &PowerConfig) -> Result<(), String> {
+    save_json_config(POWER_CONFIG, config)
+}
+
+pub fn load_rgb_config() -> RgbConfig {
+    load_json_config(RGB_CONFIG)
+}
+
+pub fn save_rgb_config(config: &RgbConfig) -> Result<(), String> {
+    save_json_config(RGB_CONFIG, config)
+}
+
+pub fn load_lightbar_config() -> LightbarConfig {
+    load_json_config(LIGHTBAR_CONFIG)
+}
+
+pub fn save_lightbar_config(config: &LightbarConfig) -> Result<(), String> {
+    save_json_config(LIGHTBAR_CONFIG, config)
+}
+
+pub fn load_vendor_fan_config() -> VendorFanConfig {
+    load_json_config(VENDOR_FAN_CONFIG)
+}
+
+pub fn save_vendor_fan_config(config: &VendorFanConfig) -> Result<(), String> {
+    save_json_config(VENDOR_FAN_CONFIG, config)
+}
+
+pub fn load_device_config() -> DeviceConfig {
+    load_json_config(DEVICE_CONFIG)
+}
+
+pub fn save_device_config(config: &DeviceConfig) -> Result<(), String> {
+    save_json_config(DEVICE_CONFIG, config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_in_dir_rejects_escapes() {
+        let dir = std::env::temp_dir().join("nuc-fan-resolve-test");
+        assert_eq!(resolve_in_dir(&dir, "a/b.csv").unwrap(), dir.join("a").join("b.csv"));
+        for name in ["", "../x.csv", "a/../../x.csv", "./x.csv", "/etc/passwd"] {
+            assert!(resolve_in_dir(&dir, name).is_err(), "{}", name);
+        }
+        #[cfg(windows)]
+        assert!(resolve_in_dir(&dir, "C:\\x.csv").is_err());
+    }
+}
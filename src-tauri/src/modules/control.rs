@@ -0,0 +1,262 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::Value;
+use tokio::sync::broadcast;
+use crate::modules::battery::{apply_battery_config, read_battery, Battery};
+use crate::modules::charge::apply_charge_schedule;
+use crate::modules::config::{load_lightbar_config, load_poll_config, load_power_config, load_telemetry_config};
+use crate::modules::device::{fan_count, has_feature, has_sensor};
+use crate::modules::ec::{ec_open, ec_thread_init, Ec};
+use crate::modules::fan::{fan_curve, fan_init, fan_mode_state, fan_reset, read_sensors, FanModeState};
+use crate::modules::lightbar::{apply_lightbar_config, ThermalLightbar};
+use crate::modules::poll::{PollScheduler, OVERHEAT_TEMP};
+use crate::modules::power::{match_rule, read_power_status, PowerStatus, PowerSwitcher};
+use crate::modules::profile::load_profile;
+use crate::modules::rgb::apply_rgb_config;
+use crate::modules::struct_set::{
+    FanControlState, LightbarConfigState, PollConfigState, PowerConfigState, PowerRule, SensorSnapshot, SensorState, TelemetryState,
+};
+use crate::modules::tdp::{load_power_profile, write_power_limits, write_tdp};
+use crate::modules::vendor_fan::apply_vendor_fan_config;
+
+const BATTERY_INTERVAL: Duration = Duration::from_secs(10);
+
+// EC 掉电或重启后设置会丢失，服务启动时重新写入已保存的设置
+// 机型不支持的功能跳过。固件风扇恢复会把 0x751 交还 EC，须在启动曲线调速之前完成
+pub fn restore_settings(ec: &dyn Ec) {
+    if has_feature("battery") {
+        apply_battery_config(ec);
+        apply_charge_schedule(ec);
+    }
+    if has_feature("rgb") {
+        apply_rgb_config(ec);
+    }
+    if has_feature("lightbar") {
+        apply_lightbar_config(ec);
+    }
+    if has_feature("vendor_fan") {
+        apply_vendor_fan_config(ec);
+    }
+}
+
+// 控制循环共享的全部状态，界面和守护进程各持有一份
+#[derive(Clone)]
+pub struct Controller {
+    pub fan: FanControlState,
+    pub poll: PollConfigState,
+    pub power: PowerConfigState,
+    pub lightbar: LightbarConfigState,
+    pub telemetry: TelemetryState,
+    pub sensor: SensorState,
+}
+
+impl Controller {
+    pub fn new() -> Self {
+        Controller {
+            fan: FanControlState {
+                is_running: Arc::new(Mutex::new(false)),
+                fan_data: Arc::new(Mutex::new(None)),
+                profile: Arc::new(Mutex::new(String::new())),
+            },
+            poll: PollConfigState {
+                config: Arc::new(Mutex::new(load_poll_config())),
+            },
+            power: PowerConfigState {
+                config: Arc::new(Mutex::new(load_power_config())),
+            },
+            lightbar: LightbarConfigState {
+                config: Arc::new(Mutex::new(load_lightbar_config())),
+            },
+            telemetry: TelemetryState::new(load_telemetry_config()),
+            sensor: SensorState {
+                latest: Arc::new(Mutex::new(None)),
+                updates: broadcast::channel(16).0,
+            },
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        *self.fan.is_running.lock().unwrap()
+    }
+
+    // 切换曲线，控制线程下一次采样时生效
+    pub fn start(&self, ec: &dyn Ec, profile: &str, fan_data: Value) {
+        fan_init(ec);
+        *self.fan.fan_data.lock().unwrap() = Some(fan_data);
+        *self.fan.profile.lock().unwrap() = profile.to_string();
+        let mut is_running = self.fan.is_running.lock().unwrap();
+        if *is_running {
+            println!("Fan control is already running.");
+            return;
+        }
+        *is_running = true;
+        self.telemetry.record_event("start", profile.to_string());
+    }
+
+    // 只停止调速，恢复 EC 自动控制由调用方决定时机
+    pub fn stop(&self) {
+        let mut is_running = self.fan.is_running.lock().unwrap();
+        if *is_running {
+            self.telemetry.record_event("stop", self.fan.profile.lock().unwrap().clone());
+        }
+        *is_running = false;
+    }
+
+    // 供电方式切换后应用规则中的风扇配置和 TDP
+    fn apply_power_rule(&self, ec: &dyn Ec, rule: &PowerRule, power: &PowerStatus) {
+        if rule.profile == "auto" {
+            self.stop();
+            fan_reset(ec);
+        } else {
+            match load_profile(&rule.profile).and_then(|d| serde_json::to_value(d).map_err(|e| e.to_string())) {
+                Ok(fan_data) => self.start(ec, &rule.profile, fan_data),
+                Err(e) => {
+                    println!("供电切换失败: {}", e);
+                    return;
+                }
+            }
+        }
+        if let Some(tdp) = &rule.tdp {
+            if let Err(e) = write_tdp(ec, tdp).and_then(|report| report.check()) {
+                println!("供电切换失败: {}", e);
+            }
+        }
+        if let Some(name) = &rule.power_profile {
+            let written = load_power_profile(name).and_then(|limits| write_power_limits(ec, &limits)?.check());
+            if let Err(e) = written {
+                println!("供电切换失败: {}", e);
+            }
+        }
+        let detail = format!("{} {}W -> {}", power.source.as_str(), power.adapter_watt, rule.profile);
+        println!("供电切换: {}", detail);
+        self.telemetry.record_event("power", detail);
+    }
+
+    pub fn latest(&self) -> Option<SensorSnapshot> {
+        self.sensor.latest.lock().unwrap().clone()
+    }
+
+    // 控制循环：每次采样只读一次硬件，运行时按曲线调速，并通过 publish 推送快照
+    pub fn spawn<F>(&self, publish: F)
+    where
+        F: Fn(&SensorSnapshot) + Send + 'static,
+    {
+        let controller = self.clone();
+        thread::spawn(move || {
+            ec_thread_init();
+            let ec = ec_open();
+            let mut scheduler = PollScheduler::new();
+            let mut duty = (0i64, 0i64);
+            let mut overheated = false;
+            let mut battery: Option<(Instant, Battery)> = None;
+            let mut switcher = PowerSwitcher::new();
+            let mut indicator = ThermalLightbar::new();
+            loop {
+                let started = Instant::now();
+                // 电池变化慢，不必每次采样都读
+                if has_sensor("battery") && battery.as_ref().map(|(t, _)| t.elapsed() >= BATTERY_INTERVAL).unwrap_or(true) {
+                    battery = Some((started, read_battery(ec.as_ref())));
+                }
+                let battery = battery.as_ref().map(|(_, b)| b.clone()).unwrap_or_default();
+                let power = read_power_status(ec.as_ref());
+                let config = controller.power.config.lock().unwrap().clone();
+                if config.enabled {
+                    let matched = match_rule(&config.rules, &power);
+                    if let Some(rule) = switcher.update(matched, Duration::from_millis(config.debounce_ms)) {
+                        controller.apply_power_rule(ec.as_ref(), &config.rules[rule], &power);
+                    }
+                }
+                let mut snapshot = controller.tick(ec.as_ref(), &mut duty, &mut overheated, &battery);
+                snapshot.tick_ms = started.elapsed().as_secs_f64() * 1000.0;
+                snapshot.power = power;
+                // 持有配置锁，手动设置灯条时不会被本次温度指示覆盖
+                let lightbar = controller.lightbar.config.lock().unwrap();
+                if lightbar.thermal.enabled {
+                    indicator.update(ec.as_ref(), &lightbar.thermal, &snapshot.sample);
+                } else {
+                    indicator.reset();
+                }
+                drop(lightbar);
+                publish(&snapshot);
+                let _ = controller.sensor.updates.send(snapshot.clone());
+                let (cpu, gpu) = (snapshot.sample.cpu_temp, snapshot.sample.gpu_temp);
+                *controller.sensor.latest.lock().unwrap() = Some(snapshot);
+                let interval = scheduler.next_interval(&controller.poll.config.lock().unwrap(), cpu, gpu);
+                thread::sleep(interval);
+            }
+        });
+    }
+
+    fn tick(&self, ec: &dyn Ec, duty: &mut (i64, i64), overheated: &mut bool, battery: &Battery) -> SensorSnapshot {
+        let telemetry = &self.telemetry;
+        let mut sample = read_sensors(ec);
+        sample.battery_percent = battery.percent.round() as i64;
+        sample.battery_current = battery.current_ma as i64;
+        let (cpu, gpu) = (sample.cpu_temp, sample.gpu_temp);
+        // 调速期间持有运行标志，stop 返回后不会再有曲线写入覆盖自动模式
+        let mut is_running = self.fan.is_running.lock().unwrap();
+        let mut running = *is_running;
+        if running {
+            let fan_data = self.fan.fan_data.lock().unwrap().clone();
+            if let Some(mut set) = fan_data.and_then(|d| fan_curve(ec, &d.get("left_fan"), &d.get("right_fan"), cpu, gpu)) {
+                // 单风扇机型不写右风扇
+                if fan_count() < 2 {
+                    set.1 = 0;
+                }
+                if set != *duty {
+                    telemetry.record_event("stage", format!("{} {} -> {} {}", duty.0, duty.1, set.0, set.1));
+                }
+                *duty = set;
+            }
+        }
+        let failed = fan_mode_state() == FanModeState::Failed;
+        if running && failed {
+            // 风扇控制模式恢复失败后 EC 已接管，停止调速，重新启动配置前不再下发占空比
+            *is_running = false;
+            running = false;
+            telemetry.record_event("stop", "fan_mode_failed".to_string());
+        }
+        drop(is_running);
+        if !running {
+            // 未运行时由 EC 自动控制
+            *duty = (0, 0);
+        }
+        let mut faults = Vec::new();
+        if failed {
+            faults.push("fan_mode_failed".to_string());
+        }
+        // 故障每次都报告，事件只在进入和退出过热时各记一次
+        let overheat = cpu > OVERHEAT_TEMP || gpu > OVERHEAT_TEMP;
+        if overheat {
+            faults.push("overheat".to_string());
+        }
+        if overheat != *overheated {
+            let kind = if overheat { "overheat" } else { "overheat_clear" };
+            telemetry.record_event(kind, format!("cpu {} gpu {}", cpu, gpu));
+            *overheated = overheat;
+        }
+        // 机型没有的传感器不参与判断
+        let out_of_range = |sensor: &str, temp: i64| has_sensor(sensor) && !(1..=110).contains(&temp);
+        if out_of_range("cpu_temp", cpu) || out_of_range("gpu_temp", gpu) {
+            faults.push("sensor_range".to_string());
+        }
+        if running && duty.0 > 0 && has_sensor("left_fan_speed") && sample.left_fan_speed == 0 {
+            faults.push("left_fan_stall".to_string());
+        }
+        if running && duty.1 > 0 && has_sensor("right_fan_speed") && sample.right_fan_speed == 0 {
+            faults.push("right_fan_stall".to_string());
+        }
+        sample.left_duty = duty.0;
+        sample.right_duty = duty.1;
+        sample.profile = if running { self.fan.profile.lock().unwrap().clone() } else { "auto".to_string() };
+        telemetry.record_sample(sample.clone());
+        SensorSnapshot { sample, running, faults, tick_ms: 0.0, power: PowerStatus::default() }
+    }
+}
+
+impl Default for Controller {
+    fn default() -> Self {
+        Controller::new()
+    }
+}
@@ -0,0 +1,195 @@
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use crate::modules::config::load_device_config;
+use crate::modules::ec::{ec_read, ec_read_cmd, is_simulated, Ec};
+use crate::modules::struct_set::{DeviceCapability, Reg1926};
+
+const ADDR_AP_PROJECT_ID: u16 = 1856;
+const ADDR_EC_BIOS_INFO5: u16 = 1126;
+const ADDR_FAN_MODE: u16 = 0x751;
+const OEM_BYTES: [(&str, u16); 10] = [
+    ("ap_oem", 1857),
+    ("bios_oem", 1870),
+    ("bios_oem2", 1922),
+    ("bios_oem3", 1955),
+    ("ap_bios", 1956),
+    ("ap_oem3", 1957),
+    ("ap_oem4", 1958),
+    ("ap_oem6", 1990),
+    ("ap_oem7", 1991),
+    ("bios_info5", ADDR_EC_BIOS_INFO5),
+];
+
+// 按机型启用的功能，其余功能(风扇曲线、传感器等)所有机型都有
+pub const FEATURES: &[&str] = &["battery", "tdp", "limits", "nv_oc", "power_profile", "power", "rgb", "lightbar", "vendor_fan"];
+
+// 内置机型能力库，和可执行文件一起发布
+const BUILTIN_DEVICES: &str = include_str!("../../devices.json");
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct DeviceInfo {
+    pub project_id: u8,
+    pub oem: BTreeMap<String, u8>,
+    // 0x751 原始读出值
+    pub fan_mode: i64,
+    pub simulated: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Device {
+    pub info: DeviceInfo,
+    // 为空表示未识别的机型
+    pub capability: Option<DeviceCapability>,
+    pub allow_unknown: bool,
+}
+
+impl Device {
+    pub fn supported(&self) -> bool {
+        self.capability.is_some() || self.allow_unknown
+    }
+
+    // 未识别但用户允许时不限制功能
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.capability.as_ref().map(|c| c.features.iter().any(|f| f == feature)).unwrap_or(true)
+    }
+}
+
+static DEVICE: OnceLock<Device> = OnceLock::new();
+
+pub fn read_device_info(ec: &dyn Ec) -> DeviceInfo {
+    DeviceInfo {
+        project_id: ec_read(ec, ADDR_AP_PROJECT_ID) as u8,
+        oem: OEM_BYTES.iter().map(|&(name, addr)| (name.to_string(), ec_read(ec, addr) as u8)).collect(),
+        fan_mode: ec.call(&ec_read_cmd(ADDR_FAN_MODE)),
+        simulated: is_simulated(),
+    }
+}
+
+// 用户条目在前，内置条目在后
+pub fn load_device_db() -> Vec<DeviceCapability> {
+    let mut devices = load_device_config().devices;
+    match serde_json::from_str::<Vec<DeviceCapability>>(BUILTIN_DEVICES) {
+        Ok(builtin) => devices.extend(builtin),
+        Err(e) => println!("devices.json: {}", e),
+    }
+    devices
+}
+
+// 按 project_id 识别，OEM/BIOS 字节和 0x751 高字节作为附加校验，条目中给出的项都要相同
+pub fn match_device(db: &[DeviceCapability], info: &DeviceInfo) -> Option<DeviceCapability> {
+    let fan_mode_high = ((info.fan_mode >> 8) & 0xFF) as u8;
+    db.iter()
+        .find(|c| {
+            c.project_ids.contains(&info.project_id)
+                && c.oem.iter().all(|(name, value)| info.oem.get(name) == Some(value))
+                && c.fan_mode_high.map(|h| h == fan_mode_high).unwrap_or(true)
+        })
+        .cloned()
+}
+
+pub fn detect_device(ec: &dyn Ec, allow_unknown: bool) -> Device {
+    let info = read_device_info(ec);
+    Device {
+        capability: match_device(&load_device_db(), &info),
+        allow_unknown: allow_unknown || load_device_config().allow_unknown,
+        info,
+    }
+}
+
+// 进程启动时调用一次，未识别且未允许时返回 Err，调用方不应再写 EC
+pub fn device_init(ec: &dyn Ec, allow_unknown: bool) -> Result<&'static Device, String> {
+    let device = DEVICE.get_or_init(|| detect_device(ec, allow_unknown));
+    match &device.capability {
+        Some(c) => eprintln!("已识别机型: {}", c.name),
+        None => eprintln!(
+            "未识别的机型: project_id {} 0x751 {:#06x} OEM {:?}",
+            device.info.project_id, device.info.fan_mode, device.info.oem
+        ),
+    }
+    if !device.supported() {
+        return Err(format!(
+            "未识别的机型(project_id {})，为避免误写 EC 已停止；确认兼容后在 device_config.json 中设置 allow_unknown 或添加机型",
+            device.info.project_id
+        ));
+    }
+    Ok(device)
+}
+
+pub fn current_device() -> Option<&'static Device> {
+    DEVICE.get()
+}
+
+// 未初始化时(如直接调用的工具函数)不做限制
+pub fn require_feature(feature: &str) -> Result<(), String> {
+    match current_device() {
+        Some(device) if FEATURES.contains(&feature) && !device.has_feature(feature) => Err(format!("当前机型不支持: {}", feature)),
+        _ => Ok(()),
+    }
+}
+
+pub fn has_feature(feature: &str) -> bool {
+    require_feature(feature).is_ok()
+}
+
+pub fn current_capability() -> Option<&'static DeviceCapability> {
+    current_device().and_then(|d| d.capability.as_ref())
+}
+
+// 只有机型条目明确为 myfan3_l1 时 1926 才是固件风扇 L1
+pub fn reg_1926_is_l1() -> bool {
+    current_capability().and_then(|c| c.reg_1926) == Some(Reg1926::Myfan3L1)
+}
+
+// 未识别的机型不限制
+pub fn has_sensor(sensor: &str) -> bool {
+    current_capability().map(|c| c.sensors.is_empty() || c.sensors.iter().any(|s| s == sensor)).unwrap_or(true)
+}
+
+pub fn fan_count() -> u8 {
+    current_capability().map(|c| c.fans).unwrap_or(2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn db() -> Vec<DeviceCapability> {
+        serde_json::from_str(BUILTIN_DEVICES).unwrap()
+    }
+
+    fn x15() -> DeviceInfo {
+        let mut info = DeviceInfo { project_id: 113, oem: BTreeMap::new(), fan_mode: 0x6C10, simulated: true };
+        for (name, _) in OEM_BYTES {
+            info.oem.insert(name.to_string(), 0);
+        }
+        info.oem.insert("bios_info5".to_string(), 21);
+        info
+    }
+
+    #[test]
+    fn x15_matches_on_project_id_and_bios_info() {
+        assert_eq!(match_device(&db(), &x15()).unwrap().name, "Intel NUC X15 (LAPAC71H)");
+    }
+
+    #[test]
+    fn fan_mode_alone_does_not_identify() {
+        let other = DeviceInfo { project_id: 1, ..x15() };
+        assert!(match_device(&db(), &other).is_none());
+        let mut bios = x15();
+        bios.oem.insert("bios_info5".to_string(), 22);
+        assert!(match_device(&db(), &bios).is_none());
+        // 0x751 高字节不同时即使 project_id 相同也不匹配
+        let fan_mode = DeviceInfo { fan_mode: 0x1210, ..x15() };
+        assert!(match_device(&db(), &fan_mode).is_none());
+    }
+
+    #[test]
+    fn user_entries_match_first() {
+        let user = DeviceCapability { name: "user".to_string(), project_ids: vec![113], fans: 1, ..Default::default() };
+        let mut devices = vec![user];
+        devices.extend(db());
+        let matched = match_device(&devices, &x15()).unwrap();
+        assert_eq!((matched.name.as_str(), matched.fans), ("user", 1));
+    }
+}
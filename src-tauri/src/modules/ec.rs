@@ -0,0 +1,188 @@
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+#[cfg(windows)]
+use windows::core::BSTR;
+#[cfg(windows)]
+use windows::Win32::System::Wmi::{IWbemClassObject, IWbemServices};
+#[cfg(windows)]
+use crate::modules::wmi::{wmi_init, wmi_security, wmi_thread_init, wmi_try_set};
+use crate::modules::sim::SimEc;
+
+#[cfg(windows)]
+pub type Wmi = (IWbemClassObject, IWbemServices, BSTR, BSTR);
+
+// 所有 EC 访问都经过 GetSetULong，参数编码见 ec_read_cmd / ec_write_cmd
+pub trait Ec {
+    fn call(&self, data: &str) -> i64;
+}
+
+#[cfg(windows)]
+pub struct WmiEc(Wmi);
+
+#[cfg(windows)]
+impl Ec for WmiEc {
+    fn call(&self, data: &str) -> i64 {
+        let (in_cls, svc, obj_path, method_name) = &self.0;
+        // 失败时返回 -1，读出的温度为 255 会让风扇曲线按过热处理
+        wmi_try_set(in_cls, svc, obj_path, method_name, data).unwrap_or_else(|e| {
+            EC_ERRORS.fetch_add(1, Ordering::Relaxed);
+            println!("EC 访问失败 {}: {:?}", data, e);
+            -1
+        })
+    }
+}
+
+static EC_ERRORS: AtomicU64 = AtomicU64::new(0);
+
+// 进程启动以来 EC 访问失败的次数
+pub fn ec_error_count() -> u64 {
+    EC_ERRORS.load(Ordering::Relaxed)
+}
+
+// 非 Windows 平台只能使用模拟 EC
+static SIMULATED: AtomicBool = AtomicBool::new(!cfg!(windows));
+
+pub fn use_simulator() {
+    SIMULATED.store(true, Ordering::SeqCst);
+}
+
+pub fn is_simulated() -> bool {
+    SIMULATED.load(Ordering::SeqCst)
+}
+
+// 进程启动时调用一次，设置 NUC_FAN_SIM=1 时使用模拟 EC
+pub fn ec_init() {
+    if env_simulated() {
+        use_simulator();
+    }
+    #[cfg(windows)]
+    if !is_simulated() {
+        wmi_security();
+    }
+}
+
+fn env_simulated() -> bool {
+    std::env::var("NUC_FAN_SIM").map(|v| v == "1").unwrap_or(false)
+}
+
+// 新线程使用 EC 前调用
+pub fn ec_thread_init() {
+    #[cfg(windows)]
+    if !is_simulated() {
+        wmi_thread_init();
+    }
+}
+
+pub fn ec_open() -> Box<dyn Ec> {
+    #[cfg(windows)]
+    if !is_simulated() {
+        return Box::new(WmiEc(wmi_init()));
+    }
+    Box::new(SimEc::shared())
+}
+
+// 读: 0x000001000000XXXX  写: 0x0000000000VVXXXX
+pub fn ec_read_cmd(addr: u16) -> String {
+    format!("0x{:016x}", (1u64 << 40) | addr as u64)
+}
+
+pub fn ec_write_cmd(addr: u16, value: u8) -> String {
+    format!("0x{:016x}", ((value as u64) << 16) | addr as u64)
+}
+
+pub fn ec_read(ec: &dyn Ec, addr: u16) -> i64 {
+    ec.call(&ec_read_cmd(addr)) & 0xFF
+}
+
+// 两个连续寄存器组成的 16 位值，第一个为高字节
+pub fn ec_read_u16(ec: &dyn Ec, addr: u16) -> u16 {
+    ((ec_read(ec, addr) as u16) << 8) | ec_read(ec, addr + 1) as u16
+}
+
+pub fn ec_write(ec: &dyn Ec, addr: u16, value: u8) -> i64 {
+    ec.call(&ec_write_cmd(addr, value))
+}
+
+// 写入后读回不一致时的重试次数和间隔
+const WRITE_ATTEMPTS: u32 = 3;
+const WRITE_RETRY_DELAY: Duration = Duration::from_millis(20);
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct WriteResult {
+    pub addr: u16,
+    pub value: u8,
+    // 最后一次读回的值，访问失败时为 -1
+    pub read_back: i64,
+    pub attempts: u32,
+    pub ok: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct WriteReport {
+    // 全部寄存器读回一致
+    pub ok: bool,
+    pub writes: Vec<WriteResult>,
+}
+
+impl WriteReport {
+    pub fn new(writes: Vec<WriteResult>) -> Self {
+        WriteReport { ok: writes.iter().all(|w| w.ok), writes }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        if self.ok {
+            return Ok(());
+        }
+        let failed: Vec<String> = self
+            .writes
+            .iter()
+            .filter(|w| !w.ok)
+            .map(|w| format!("0x{:x} 写入 {} 读回 {}", w.addr, w.value, w.read_back))
+            .collect();
+        Err(format!("寄存器写入校验失败: {}", failed.join(", ")))
+    }
+}
+
+// 写入后读回同一寄存器，不一致时重写；0x751 这类命令寄存器读回的不是写入值，不能用这个
+pub fn ec_write_verified(ec: &dyn Ec, addr: u16, value: u8) -> WriteResult {
+    let mut read_back = -1;
+    for attempt in 1..=WRITE_ATTEMPTS {
+        ec_write(ec, addr, value);
+        // 不用 ec_read，访问失败的 -1 不能被截成 255
+        let raw = ec.call(&ec_read_cmd(addr));
+        read_back = if raw < 0 { -1 } else { raw & 0xFF };
+        if read_back == value as i64 {
+            return WriteResult { addr, value, read_back, attempts: attempt, ok: true };
+        }
+        if attempt < WRITE_ATTEMPTS {
+            thread::sleep(WRITE_RETRY_DELAY);
+        }
+    }
+    WriteResult { addr, value, read_back, attempts: WRITE_ATTEMPTS, ok: false }
+}
+
+// 按顺序逐个写入并校验，某个失败也继续写后面的寄存器
+pub fn ec_write_all(ec: &dyn Ec, writes: &[(u16, u8)]) -> WriteReport {
+    WriteReport::new(writes.iter().map(|&(addr, value)| ec_write_verified(ec, addr, value)).collect())
+}
+
+// 支持十进制和 0x 开头的十六进制
+pub fn parse_num(s: &str) -> Result<u64, String> {
+    let parsed = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse::<u64>(),
+    };
+    parsed.map_err(|_| format!("无效的数值: {}", s))
+}
+
+pub fn parse_addr(s: &str) -> Result<u16, String> {
+    let addr = parse_num(s)?;
+    u16::try_from(addr).map_err(|_| format!("地址超出范围: {}", s))
+}
+
+pub fn parse_byte(s: &str) -> Result<u8, String> {
+    let value = parse_num(s)?;
+    u8::try_from(value).map_err(|_| format!("数值超出范围 0~255: {}", s))
+}
@@ -0,0 +1,197 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use serde::{Deserialize, Serialize};
+use crate::modules::config::get_config_dir;
+use crate::modules::ec::{ec_read_cmd, is_simulated, Ec};
+use crate::modules::export::get_export_dir;
+use crate::modules::profile::check_name;
+use crate::modules::telemetry::now_ms;
+
+// 寄存器表直接取自分析.md 中的 "ADDR_XXX = N;" 行
+const REGISTER_MAP: &str = include_str!("../../../assets/分析.md");
+// 分析.md 中没有、代码里按十六进制地址使用的寄存器
+const EXTRA_LABELS: [(u16, &str); 5] = [
+    (0x733, "GPU2"),
+    (0x73D, "GPU1"),
+    (0x751, "FAN_MODE"),
+    (0x1804, "RIGHT_FAN_DUTY"),
+    (0x1809, "LEFT_FAN_DUTY"),
+];
+
+// 单次最多读取的寄存器数，每次读取都是一次 WMI 调用
+pub const MAX_DUMP_LEN: u32 = 0x1000;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct RegisterRange {
+    pub start: u16,
+    pub len: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RegisterValue {
+    pub addr: u16,
+    // 原始读出值，读取失败为 -1
+    pub value: i64,
+    pub label: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RegisterSnapshot {
+    pub name: String,
+    pub ts: u64,
+    pub simulated: bool,
+    pub registers: BTreeMap<u16, i64>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RegisterDiff {
+    pub addr: u16,
+    pub label: Option<String>,
+    pub before: i64,
+    pub after: i64,
+}
+
+// 同一地址有多个名字时用 / 连接，如 1926 既是 TCC 偏移也是 MyFan3 L1
+pub fn register_labels() -> &'static BTreeMap<u16, String> {
+    static LABELS: OnceLock<BTreeMap<u16, String>> = OnceLock::new();
+    LABELS.get_or_init(|| {
+        let mut labels: BTreeMap<u16, String> = BTreeMap::new();
+        let parsed = REGISTER_MAP.lines().filter_map(|line| {
+            let (name, value) = line.trim().strip_suffix(';')?.split_once('=')?;
+            let name = name.trim().strip_prefix("ADDR_")?;
+            Some((value.trim().parse::<u16>().ok()?, name.to_string()))
+        });
+        for (addr, name) in parsed.chain(EXTRA_LABELS.iter().map(|&(a, n)| (a, n.to_string()))) {
+            labels
+                .entry(addr)
+                .and_modify(|l| {
+                    if !l.split('/').any(|n| n == name) {
+                        *l = format!("{}/{}", l, name);
+                    }
+                })
+                .or_insert(name);
+        }
+        labels
+    })
+}
+
+fn label(addr: u16) -> Option<String> {
+    register_labels().get(&addr).cloned()
+}
+
+pub fn check_range(range: &RegisterRange) -> Result<(), String> {
+    if range.len == 0 || range.len > MAX_DUMP_LEN {
+        return Err(format!("len 超出范围 1~{}: {}", MAX_DUMP_LEN, range.len));
+    }
+    if range.start as u32 + range.len > 0x10000 {
+        return Err(format!("范围超出地址空间: {:#06x} + {}", range.start, range.len));
+    }
+    Ok(())
+}
+
+// 只读，逐个寄存器按读编码访问
+pub fn dump_registers(ec: &dyn Ec, range: &RegisterRange) -> Result<Vec<RegisterValue>, String> {
+    check_range(range)?;
+    Ok((range.start as u32..range.start as u32 + range.len)
+        .map(|addr| {
+            let addr = addr as u16;
+            RegisterValue { addr, value: ec.call(&ec_read_cmd(addr)), label: label(addr) }
+        })
+        .collect())
+}
+
+// 未给出范围时读取寄存器表中的全部地址
+pub fn read_snapshot(ec: &dyn Ec, name: &str, ranges: &[RegisterRange]) -> Result<RegisterSnapshot, String> {
+    let mut registers = BTreeMap::new();
+    if ranges.is_empty() {
+        for &addr in register_labels().keys() {
+            registers.insert(addr, ec.call(&ec_read_cmd(addr)));
+        }
+    }
+    for range in ranges {
+        registers.extend(dump_registers(ec, range)?.into_iter().map(|r| (r.addr, r.value)));
+    }
+    Ok(RegisterSnapshot { name: name.to_string(), ts: now_ms(), simulated: is_simulated(), registers })
+}
+
+// 只比较两个快照都读过的地址
+pub fn diff_snapshots(before: &RegisterSnapshot, after: &RegisterSnapshot) -> Vec<RegisterDiff> {
+    before
+        .registers
+        .iter()
+        .filter_map(|(&addr, &b)| {
+            let a = *after.registers.get(&addr)?;
+            (a != b).then(|| RegisterDiff { addr, label: label(addr), before: b, after: a })
+        })
+        .collect()
+}
+
+// 和 EC 当前值比较，只读快照中已有的地址
+pub fn diff_live(ec: &dyn Ec, before: &RegisterSnapshot) -> Vec<RegisterDiff> {
+    let registers = before.registers.keys().map(|&addr| (addr, ec.call(&ec_read_cmd(addr)))).collect();
+    let live = RegisterSnapshot { name: "live".to_string(), ts: now_ms(), simulated: is_simulated(), registers };
+    diff_snapshots(before, &live)
+}
+
+pub fn get_snapshot_dir() -> PathBuf {
+    get_config_dir().join("snapshots")
+}
+
+fn snapshot_path(name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    let dir = get_snapshot_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+pub fn list_snapshots() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(get_snapshot_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_string_lossy().strip_suffix(".json").map(|n| n.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn load_snapshot(name: &str) -> Result<RegisterSnapshot, String> {
+    let path = snapshot_path(name)?;
+    let json_data = fs::read_to_string(&path).map_err(|_| format!("快照不存在: {}", name))?;
+    serde_json::from_str(&json_data).map_err(|e| e.to_string())
+}
+
+pub fn save_snapshot(snapshot: &RegisterSnapshot) -> Result<(), String> {
+    let json_data = serde_json::to_string_pretty(snapshot).map_err(|e| e.to_string())?;
+    fs::write(snapshot_path(&snapshot.name)?, json_data).map_err(|e| e.to_string())
+}
+
+pub fn delete_snapshot(name: &str) -> Result<(), String> {
+    fs::remove_file(snapshot_path(name)?).map_err(|_| format!("快照不存在: {}", name))
+}
+
+pub fn default_snapshot_export_path(name: &str) -> Result<PathBuf, String> {
+    let dir = get_export_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!("ec-{}.json", name)))
+}
+
+pub fn export_snapshot(name: &str, path: &Path) -> Result<(), String> {
+    let json_data = serde_json::to_string_pretty(&load_snapshot(name)?).map_err(|e| e.to_string())?;
+    fs::write(path, json_data).map_err(|e| e.to_string())
+}
+
+// 导入时可改名，避免覆盖同名快照
+pub fn import_snapshot(path: &Path, name: Option<&str>) -> Result<RegisterSnapshot, String> {
+    let json_data = fs::read_to_string(path).map_err(|e| format!("{:?}: {}", path, e))?;
+    let mut snapshot: RegisterSnapshot = serde_json::from_str(&json_data).map_err(|e| e.to_string())?;
+    if let Some(name) = name {
+        snapshot.name = name.to_string();
+    }
+    save_snapshot(&snapshot)?;
+    Ok(snapshot)
+}
@@ -0,0 +1,241 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, Utc};
+use serde_json::{Map, Value};
+use crate::modules::config::get_config_dir;
+use crate::modules::struct_set::{TelemetryEvent, TelemetrySample};
+
+pub const EXPORT_COLUMNS: [&str; 16] = [
+    "ts_utc", "ts_local", "cpu_temp", "gpu_temp", "left_fan_speed", "right_fan_speed",
+    "left_duty", "right_duty", "profile", "apc_watt", "cpu_pl1", "cpu_pl2",
+    "battery_percent", "battery_current", "event", "detail",
+];
+
+pub enum ExportFormat {
+    Csv,
+    Ndjson,
+}
+
+impl ExportFormat {
+    pub fn parse(format: &str) -> Result<Self, String> {
+        match format.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "json" | "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            _ => Err(format!("不支持的导出格式: {}", format)),
+        }
+    }
+
+    fn ext(&self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+        }
+    }
+}
+
+pub fn check_columns(columns: Option<Vec<String>>) -> Result<Vec<String>, String> {
+    let columns = columns
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| EXPORT_COLUMNS.iter().map(|c| c.to_string()).collect());
+    if let Some(c) = columns.iter().find(|c| !EXPORT_COLUMNS.contains(&c.as_str())) {
+        return Err(format!("未知的列: {}", c));
+    }
+    Ok(columns)
+}
+
+pub fn get_export_dir() -> PathBuf {
+    get_config_dir().join("exports")
+}
+
+pub fn default_export_path(format: &ExportFormat, ts: u64) -> Result<PathBuf, String> {
+    let dir = get_export_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!("telemetry-{}.{}", ts, format.ext())))
+}
+
+fn ts_utc(ts: u64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(ts as i64)
+        .map(|t| t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+        .unwrap_or_default()
+}
+
+fn ts_local(ts: u64) -> String {
+    DateTime::<Utc>::from_timestamp_millis(ts as i64)
+        .map(|t| t.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S%.3f%:z").to_string())
+        .unwrap_or_default()
+}
+
+fn sample_value(sample: &TelemetrySample, column: &str) -> Value {
+    match column {
+        "ts_utc" => ts_utc(sample.ts).into(),
+        "ts_local" => ts_local(sample.ts).into(),
+        "cpu_temp" => sample.cpu_temp.into(),
+        "gpu_temp" => sample.gpu_temp.into(),
+        "left_fan_speed" => sample.left_fan_speed.into(),
+        "right_fan_speed" => sample.right_fan_speed.into(),
+        "left_duty" => sample.left_duty.into(),
+        "right_duty" => sample.right_duty.into(),
+        "profile" => sample.profile.clone().into(),
+        "apc_watt" => sample.apc_watt.into(),
+        "cpu_pl1" => sample.cpu_pl1.into(),
+        "cpu_pl2" => sample.cpu_pl2.into(),
+        "battery_percent" => sample.battery_percent.into(),
+        "battery_current" => sample.battery_current.into(),
+        _ => Value::Null,
+    }
+}
+
+fn event_value(event: &TelemetryEvent, column: &str) -> Value {
+    match column {
+        "ts_utc" => ts_utc(event.ts).into(),
+        "ts_local" => ts_local(event.ts).into(),
+        "event" => event.kind.clone().into(),
+        "detail" => event.detail.clone().into(),
+        _ => Value::Null,
+    }
+}
+
+fn csv_field(value: &Value) -> String {
+    let text = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        v => v.to_string(),
+    };
+    if text.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text
+    }
+}
+
+// 采样与事件按时间合并后逐行写出，返回写入的行数
+pub fn write_export(
+    samples: &[TelemetrySample],
+    events: &[TelemetryEvent],
+    columns: &[String],
+    format: &ExportFormat,
+    path: &Path,
+) -> Result<usize, String> {
+    let mut rows: Vec<(u64, Vec<Value>)> = samples
+        .iter()
+        .map(|s| (s.ts, columns.iter().map(|c| sample_value(s, c)).collect()))
+        .chain(events.iter().map(|e| (e.ts, columns.iter().map(|c| event_value(e, c)).collect())))
+        .collect();
+    rows.sort_by_key(|(ts, _)| *ts);
+
+    let mut out = BufWriter::new(File::create(path).map_err(|e| e.to_string())?);
+    if let ExportFormat::Csv = format {
+        writeln!(out, "{}", columns.join(",")).map_err(|e| e.to_string())?;
+    }
+    for (_, values) in &rows {
+        let line = match format {
+            ExportFormat::Csv => values.iter().map(csv_field).collect::<Vec<_>>().join(","),
+            ExportFormat::Ndjson => {
+                let map: Map<String, Value> = columns.iter().cloned().zip(values.iter().cloned()).collect();
+                Value::Object(map).to_string()
+            }
+        };
+        writeln!(out, "{}", line).map_err(|e| e.to_string())?;
+    }
+    out.flush().map_err(|e| e.to_string())?;
+    Ok(rows.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 2024-01-02T03:04:05.678Z
+    const TS: u64 = 1704164645678;
+
+    fn sample(ts: u64, cpu_temp: i64, profile: &str) -> TelemetrySample {
+        TelemetrySample {
+            ts,
+            cpu_temp,
+            gpu_temp: 40,
+            left_fan_speed: 3000,
+            right_fan_speed: 3100,
+            left_duty: 40,
+            right_duty: 45,
+            profile: profile.to_string(),
+            apc_watt: 120,
+            cpu_pl1: 45,
+            cpu_pl2: 115,
+            battery_percent: 80,
+            battery_current: -1500,
+        }
+    }
+
+    fn event(ts: u64, kind: &str, detail: &str) -> TelemetryEvent {
+        TelemetryEvent { ts, kind: kind.to_string(), detail: detail.to_string() }
+    }
+
+    fn columns(names: &[&str]) -> Vec<String> {
+        names.iter().map(|c| c.to_string()).collect()
+    }
+
+    fn export(format: ExportFormat, columns: &[String], name: &str) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("nuc-fan-export-{}-{}", name, std::process::id()));
+        let samples = [sample(TS, 50, "custom"), sample(TS + 2000, 60, "quiet, night")];
+        let events = [event(TS + 1000, "stage", "20 20 -> 40 45"), event(TS + 2000, "stop", "say \"bye\"\nnow")];
+        let rows = write_export(&samples, &events, columns, &format, &path).unwrap();
+        assert_eq!(rows, 4);
+        let text = fs::read_to_string(&path).unwrap();
+        let _ = fs::remove_file(&path);
+        text.lines().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn csv_quotes_special_characters() {
+        assert_eq!(csv_field(&Value::Null), "");
+        assert_eq!(csv_field(&42.into()), "42");
+        assert_eq!(csv_field(&"custom".into()), "custom");
+        assert_eq!(csv_field(&"a,b".into()), "\"a,b\"");
+        assert_eq!(csv_field(&"say \"hi\"".into()), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field(&"a\nb".into()), "\"a\nb\"");
+    }
+
+    #[test]
+    fn columns_default_to_all_and_reject_unknown() {
+        assert_eq!(check_columns(None).unwrap(), columns(&EXPORT_COLUMNS));
+        assert_eq!(check_columns(Some(Vec::new())).unwrap(), columns(&EXPORT_COLUMNS));
+        // 保留调用方给出的顺序
+        assert_eq!(check_columns(Some(columns(&["gpu_temp", "ts_utc"]))).unwrap(), columns(&["gpu_temp", "ts_utc"]));
+        assert!(check_columns(Some(columns(&["ts_utc", "fan_rpm"]))).is_err());
+    }
+
+    #[test]
+    fn timestamps_in_utc_and_local_time() {
+        assert_eq!(ts_utc(TS), "2024-01-02T03:04:05.678Z");
+        // 本地时间带时区偏移，解析后是同一时刻
+        let local = DateTime::parse_from_str(&ts_local(TS), "%Y-%m-%d %H:%M:%S%.3f%:z").unwrap();
+        assert_eq!(local.timestamp_millis(), TS as i64);
+    }
+
+    #[test]
+    fn csv_merges_samples_and_events_by_time() {
+        let lines = export(ExportFormat::Csv, &columns(&["ts_utc", "cpu_temp", "profile", "event", "detail"]), "csv");
+        assert_eq!(lines, [
+            "ts_utc,cpu_temp,profile,event,detail",
+            "2024-01-02T03:04:05.678Z,50,custom,,",
+            "2024-01-02T03:04:06.678Z,,,stage,20 20 -> 40 45",
+            // 同一时刻采样在前
+            "2024-01-02T03:04:07.678Z,60,\"quiet, night\",,",
+            "2024-01-02T03:04:07.678Z,,,stop,\"say \"\"bye\"\"",
+            "now\"",
+        ]);
+    }
+
+    #[test]
+    fn ndjson_keeps_selected_columns() {
+        let lines = export(ExportFormat::Ndjson, &columns(&["cpu_temp", "event"]), "ndjson");
+        let rows: Vec<Value> = lines.iter().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(rows, [
+            serde_json::json!({ "cpu_temp": 50, "event": null }),
+            serde_json::json!({ "cpu_temp": null, "event": "stage" }),
+            serde_json::json!({ "cpu_temp": 60, "event": null }),
+            serde_json::json!({ "cpu_temp": null, "event": "stop" }),
+        ]);
+    }
+}
@@ -0,0 +1,306 @@
+use std::sync::Mutex;
+use notify_rust::Notification;
+use serde::{Deserialize, Serialize};
+use crate::modules::device::{fan_count, has_sensor};
+use crate::modules::ec::{ec_read_cmd, ec_write, ec_write_all, Ec, WriteReport};
+use crate::modules::poll::OVERHEAT_TEMP;
+use crate::modules::telemetry::now_ms;
+use crate::modules::struct_set::TelemetrySample;
+
+fn speed_c(speed_n: i64, speed_l: i64, temp_n: i64, temp_l: i64, temp: i64) -> i64 {
+    speed_l + (((speed_n - speed_l) as f64 / ((temp_n - temp_l) as f64 + 0.001)) * (temp - temp_l) as f64) as i64
+}
+
+// 读取一次全部传感器，占空比和配置名由控制循环填写；机型没有的传感器不读，记为 0
+pub fn read_sensors(ec: &dyn Ec) -> TelemetrySample {
+    let speed = |sensor: &str, high: &str, low: &str| if has_sensor(sensor) { (ec.call(high) & 0xFF) << 8 | ec.call(low) } else { 0 };
+    let left_fan_speed = speed("left_fan_speed", "0x000001000000046C", "0x000001000000046D");
+    let right_fan_speed = if fan_count() >= 2 { speed("right_fan_speed", "0x0000010000000464", "0x0000010000000465") } else { 0 };
+    let cpu_temp = if has_sensor("cpu_temp") { ec.call("0x000001000000043E") } else { 0 };
+    let gpu_temp = if has_sensor("gpu_temp") { ec.call("0x000001000000044F") & 0xFF } else { 0 };
+    let apc_watt = if has_sensor("apc_watt") { ec.call("0x000001000000044C") & 0xFF } else { 0 };
+    let cpu_pl1 = ec.call("0x0000010000000783") & 0xFF;
+    let cpu_pl2 = ec.call("0x0000010000000784") & 0xFF;
+    TelemetrySample {
+        ts: now_ms(),
+        cpu_temp,
+        gpu_temp,
+        left_fan_speed,
+        right_fan_speed,
+        left_duty: 0,
+        right_duty: 0,
+        profile: String::new(),
+        apc_watt,
+        cpu_pl1,
+        cpu_pl2,
+        battery_percent: 0,
+        battery_current: 0,
+    }
+}
+
+// 读回不一致只记录，仍返回下发的值；风扇控制模式恢复失败时没有下发，返回 None
+fn apply_duty(ec: &dyn Ec, left: i64, right: i64) -> Option<(i64, i64)> {
+    match fan_set(ec, left as i16, right as i16) {
+        Ok(report) => {
+            if let Err(e) = report.check() {
+                eprintln!("{}", e);
+            }
+            Some((left, right))
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            None
+        }
+    }
+}
+
+// 按曲线计算并下发左右风扇占空比，返回本次下发的值
+pub fn fan_curve(ec: &dyn Ec, left: &Option<&serde_json::Value>, right: &Option<&serde_json::Value>, cpu_out: i64, gpu_out: i64) -> Option<(i64, i64)> {
+    if cpu_out > OVERHEAT_TEMP || gpu_out > OVERHEAT_TEMP {
+        return apply_duty(ec, 100, 100);
+    }
+    let (mut l_c, mut s_c, mut r_c, mut s_c_) = (0i64, 0i64, 0i64, 0i64);
+    if let (Some(left), Some(right)) = (left.expect("l").as_array(), right.expect("r").as_array()) {
+        for l_ in left {
+            if let (Some(l), Some(s)) = (
+                l_.get("temperature").expect("转换错误").as_i64(), l_.get("speed").expect("转换错误").as_i64()
+            ) {
+                if l >= cpu_out {
+                    for r_ in right {
+                        if let (Some(r), Some(s_)) = (
+                            r_.get("temperature").expect("转换错误").as_i64(), r_.get("speed").expect("转换错误").as_i64()
+                        ) {
+                            if r >= gpu_out {
+                                let s = speed_c(s, s_c, l, l_c, cpu_out);
+                                let s_ = speed_c(s_, s_c_, r, r_c, gpu_out);
+                                return apply_duty(ec, s, s_);
+                            } else {
+                                (l_c, s_c, r_c, s_c_) = (l, s, r, s_);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+// 风扇控制模式寄存器: 读出值高字节固定为 0x6C，低字节 0x10 为 EC 自动，带 0x40 位为手动，
+// 0x00 出现在睡眠唤醒后，此时 EC 既不按自己的表也不按手动占空比调速
+const ADDR_FAN_MODE: u16 = 0x751;
+const FAN_MODE_SIGNATURE: i64 = 0x6C;
+const FAN_MODE_AUTO: i64 = 0x10;
+const FAN_MODE_MANUAL_BIT: i64 = 0x40;
+const FAN_MODE_IDLE: i64 = 0x00;
+// 写入的命令值
+const CMD_MANUAL: u8 = 0x40;
+const CMD_RESET: u8 = 0xA0;
+
+// 连续恢复失败这么多次后交还 EC 自动控制，直到下次 fan_init
+const MAX_RECOVERY_ATTEMPTS: u32 = 3;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FanMode {
+    Auto,
+    Manual,
+    Idle,
+    // 读取失败
+    Unavailable,
+    // 其它读出值，保留原始值
+    Unknown(i64),
+}
+
+impl FanMode {
+    pub fn decode(raw: i64) -> FanMode {
+        if raw < 0 {
+            return FanMode::Unavailable;
+        }
+        if raw >> 8 != FAN_MODE_SIGNATURE {
+            return FanMode::Unknown(raw);
+        }
+        match raw & 0xFF {
+            low if low & FAN_MODE_MANUAL_BIT != 0 => FanMode::Manual,
+            FAN_MODE_AUTO => FanMode::Auto,
+            FAN_MODE_IDLE => FanMode::Idle,
+            _ => FanMode::Unknown(raw),
+        }
+    }
+}
+
+// 本进程对风扇控制模式的期望: 已交还 EC、手动控制中、恢复中(已尝试次数)、恢复失败
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FanModeState {
+    Released,
+    Controlling,
+    Recovering(u32),
+    Failed,
+}
+
+static FAN_MODE_STATE: Mutex<FanModeState> = Mutex::new(FanModeState::Released);
+
+pub fn fan_mode_state() -> FanModeState {
+    *FAN_MODE_STATE.lock().unwrap()
+}
+
+pub fn read_fan_mode(ec: &dyn Ec) -> FanMode {
+    let raw = ec.call(&ec_read_cmd(ADDR_FAN_MODE));
+    let mode = FanMode::decode(raw);
+    if let FanMode::Unknown(_) | FanMode::Unavailable = mode {
+        println!("0x751 读出异常值 {:#06x}: {:?}", raw, mode);
+    }
+    mode
+}
+
+// 写命令后读回，模式不是 expected 时返回读到的模式
+fn fan_mode_command(ec: &dyn Ec, cmd: u8, expected: FanMode) -> Result<(), FanMode> {
+    ec_write(ec, ADDR_FAN_MODE, cmd);
+    match read_fan_mode(ec) {
+        mode if mode == expected => Ok(()),
+        mode => Err(mode),
+    }
+}
+
+fn notify(body: &str) {
+    if let Err(e) = Notification::new().summary("NUC X15 Fan Control").body(body).icon("firefox").show() {
+        println!("{}: {}", body, e);
+    }
+}
+
+// 切换到手动控制
+pub fn fan_init(ec: &dyn Ec) {
+    let mode = read_fan_mode(ec);
+    let next = match mode {
+        FanMode::Manual => FanModeState::Controlling,
+        _ => match fan_mode_command(ec, CMD_MANUAL, FanMode::Manual) {
+            Ok(()) => FanModeState::Controlling,
+            Err(m) => {
+                println!("切换到手动控制失败: {:?} -> {:?}", mode, m);
+                FanModeState::Recovering(1)
+            }
+        },
+    };
+    *FAN_MODE_STATE.lock().unwrap() = next;
+}
+
+// 交还 EC 自动控制
+pub fn fan_reset(ec: &dyn Ec) {
+    if let Err(m) = fan_mode_command(ec, CMD_RESET, FanMode::Auto) {
+        println!("恢复自动控制后风扇模式为 {:?}", m);
+    }
+    *FAN_MODE_STATE.lock().unwrap() = FanModeState::Released;
+}
+
+// 每次下发占空比前确认仍是手动控制，EC 自行切走时重新切回；恢复失败后不再下发占空比
+fn check_fan_mode(ec: &dyn Ec) -> Result<(), String> {
+    let mut state = FAN_MODE_STATE.lock().unwrap();
+    if *state != FanModeState::Failed {
+        *state = next_fan_mode_state(ec, *state);
+    }
+    match *state {
+        FanModeState::Failed => Err("风扇控制模式恢复失败，已交还 EC 自动控制，重新启动配置前不写入占空比".to_string()),
+        _ => Ok(()),
+    }
+}
+
+// 按读到的 0x751 推进状态，Failed 由调用方处理
+fn next_fan_mode_state(ec: &dyn Ec, state: FanModeState) -> FanModeState {
+    let mode = read_fan_mode(ec);
+    match (state, mode) {
+        (FanModeState::Recovering(_), FanMode::Manual) => {
+            notify("风扇状态异常已恢复");
+            FanModeState::Controlling
+        }
+        (_, FanMode::Manual) => FanModeState::Controlling,
+        (state, mode) => {
+            let attempts = match state {
+                FanModeState::Recovering(n) => n + 1,
+                FanModeState::Controlling => {
+                    println!("风扇控制模式被切换为 {:?}，尝试恢复", mode);
+                    1
+                }
+                _ => 1,
+            };
+            if attempts > MAX_RECOVERY_ATTEMPTS {
+                println!("风扇控制模式恢复失败 {} 次，已交还 EC 自动控制", MAX_RECOVERY_ATTEMPTS);
+                let _ = fan_mode_command(ec, CMD_RESET, FanMode::Auto);
+                notify("风扇状态异常且恢复失败，已交还 EC 自动控制");
+                FanModeState::Failed
+            } else {
+                match fan_mode_command(ec, CMD_MANUAL, FanMode::Manual) {
+                    Ok(()) if state == FanModeState::Released => FanModeState::Controlling,
+                    Ok(()) => {
+                        notify("风扇状态异常已恢复");
+                        FanModeState::Controlling
+                    }
+                    Err(m) => {
+                        println!("第 {} 次恢复风扇控制模式失败: {:?}", attempts, m);
+                        FanModeState::Recovering(attempts)
+                    }
+                }
+            }
+        }
+    }
+}
+
+const ADDR_LEFT_DUTY: u16 = 0x1809;
+const ADDR_RIGHT_DUTY: u16 = 0x1804;
+
+// 占空比寄存器按 0~200 计，单风扇机型只写左风扇
+pub fn fan_set(ec: &dyn Ec, left: i16, right: i16) -> Result<WriteReport, String> {
+    check_fan_mode(ec)?;
+    let left = left * 2;
+    let right = right * 2;
+    if fan_count() < 2 {
+        return Ok(ec_write_all(ec, &[(ADDR_LEFT_DUTY, left as u8)]));
+    }
+    Ok(ec_write_all(ec, &[(ADDR_LEFT_DUTY, left as u8), (ADDR_RIGHT_DUTY, right as u8)]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sim::{SimEc, SimState};
+
+    // 模拟 EC 卡在 EC 自动模式，写 0x751 不生效
+    fn stuck_in_auto(ec: &SimEc, stuck: bool) {
+        ec.state().lock().unwrap().set_fan_mode_stuck(stuck.then_some(FAN_MODE_AUTO as u8));
+    }
+
+    #[test]
+    fn released_takes_manual_control() {
+        let ec = SimEc::new(SimState::new());
+        assert_eq!(read_fan_mode(&ec), FanMode::Auto);
+        assert_eq!(next_fan_mode_state(&ec, FanModeState::Released), FanModeState::Controlling);
+        assert_eq!(read_fan_mode(&ec), FanMode::Manual);
+    }
+
+    #[test]
+    fn controlling_recovers_after_ec_takes_over() {
+        let ec = SimEc::new(SimState::new());
+        assert_eq!(next_fan_mode_state(&ec, FanModeState::Released), FanModeState::Controlling);
+        stuck_in_auto(&ec, true);
+        let state = next_fan_mode_state(&ec, FanModeState::Controlling);
+        assert_eq!(state, FanModeState::Recovering(1));
+        let state = next_fan_mode_state(&ec, state);
+        assert_eq!(state, FanModeState::Recovering(2));
+        stuck_in_auto(&ec, false);
+        assert_eq!(next_fan_mode_state(&ec, state), FanModeState::Controlling);
+        assert_eq!(read_fan_mode(&ec), FanMode::Manual);
+    }
+
+    #[test]
+    fn fails_after_max_recovery_attempts() {
+        let ec = SimEc::new(SimState::new());
+        stuck_in_auto(&ec, true);
+        let mut state = next_fan_mode_state(&ec, FanModeState::Controlling);
+        for attempts in 2..=MAX_RECOVERY_ATTEMPTS {
+            state = next_fan_mode_state(&ec, state);
+            assert_eq!(state, FanModeState::Recovering(attempts));
+        }
+        assert_eq!(next_fan_mode_state(&ec, state), FanModeState::Failed);
+        assert_eq!(read_fan_mode(&ec), FanMode::Auto);
+    }
+}
@@ -0,0 +1,348 @@
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::thread;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::sync::broadcast::error::RecvError;
+use crate::modules::config::{load_metrics_config, load_mqtt_config, load_rest_config};
+use crate::modules::control::{restore_settings, Controller};
+use crate::modules::device::device_init;
+use crate::modules::ec::{ec_init, ec_open, ec_thread_init};
+use crate::modules::metrics::start_metrics;
+use crate::modules::mqtt::start_mqtt;
+use crate::modules::rest::start_rest;
+use crate::modules::rpc::{dispatch, RpcError, API_VERSION, INTERNAL_ERROR, PARSE_ERROR};
+
+// 本地 JSON-RPC 2.0 接口，每行一个请求或响应
+// Linux 使用 Unix 套接字，Windows 使用命名管道
+#[cfg(windows)]
+pub const PIPE_NAME: &str = r"\\.\pipe\nuc-fan";
+
+#[cfg(unix)]
+type Stream = std::os::unix::net::UnixStream;
+#[cfg(windows)]
+type Stream = std::fs::File;
+
+// 可用 NUC_FAN_SOCKET 覆盖，默认放在运行时目录
+#[cfg(unix)]
+pub fn socket_path() -> PathBuf {
+    if let Ok(path) = std::env::var("NUC_FAN_SOCKET") {
+        return PathBuf::from(path);
+    }
+    dirs::runtime_dir().unwrap_or_else(std::env::temp_dir).join("nuc-fan.sock")
+}
+
+#[cfg(windows)]
+pub fn socket_path() -> PathBuf {
+    PathBuf::from(std::env::var("NUC_FAN_SOCKET").unwrap_or_else(|_| PIPE_NAME.to_string()))
+}
+
+fn response(id: &Value, result: Result<Value, RpcError>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({ "jsonrpc": "2.0", "id": id, "error": e.to_value() }),
+    }
+}
+
+async fn send_line<W: AsyncWrite + Unpin>(writer: &mut W, value: &Value) -> std::io::Result<()> {
+    let mut line = value.to_string();
+    line.push('\n');
+    writer.write_all(line.as_bytes()).await
+}
+
+// 订阅后连接只用于推送，topics 可选 snapshot / event，默认全部
+async fn push_updates<W: AsyncWrite + Unpin>(writer: &mut W, controller: &Controller, params: &Value) {
+    let topics: Vec<String> = params.get("topics")
+        .and_then(|t| serde_json::from_value(t.clone()).ok())
+        .unwrap_or_else(|| vec!["snapshot".to_string(), "event".to_string()]);
+    let mut snapshots = controller.sensor.updates.subscribe();
+    let mut events = controller.telemetry.events.subscribe();
+    let want_snapshot = topics.iter().any(|t| t == "snapshot");
+    let want_event = topics.iter().any(|t| t == "event");
+    loop {
+        let notification = tokio::select! {
+            r = snapshots.recv(), if want_snapshot => match r {
+                Ok(snapshot) => json!({ "jsonrpc": "2.0", "method": "snapshot", "params": snapshot }),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            r = events.recv(), if want_event => match r {
+                Ok(event) => json!({ "jsonrpc": "2.0", "method": "event", "params": event }),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            },
+            else => return,
+        };
+        if send_line(writer, &notification).await.is_err() {
+            return;
+        }
+    }
+}
+
+async fn handle_conn<S>(stream: S, controller: Controller)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut lines = tokio::io::BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request: Value = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let reply = response(&Value::Null, Err(RpcError::new(PARSE_ERROR, e.to_string())));
+                if send_line(&mut writer, &reply).await.is_err() {
+                    return;
+                }
+                continue;
+            }
+        };
+        let id = request.get("id").cloned().unwrap_or(Value::Null);
+        let method = request.get("method").and_then(|m| m.as_str()).unwrap_or_default().to_string();
+        let params = request.get("params").cloned().unwrap_or(Value::Null);
+        if method == "subscribe" {
+            if send_line(&mut writer, &response(&id, Ok(json!(true)))).await.is_ok() {
+                push_updates(&mut writer, &controller, &params).await;
+            }
+            return;
+        }
+        // EC 访问是阻塞调用，放到阻塞线程池
+        let c = controller.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            ec_thread_init();
+            dispatch(&c, &method, &params)
+        })
+        .await
+        .unwrap_or_else(|e| Err(RpcError::new(INTERNAL_ERROR, e.to_string())));
+        // 没有 id 的通知不回复
+        if request.get("id").is_none() {
+            continue;
+        }
+        if send_line(&mut writer, &response(&id, result)).await.is_err() {
+            return;
+        }
+    }
+}
+
+#[cfg(unix)]
+fn bind() -> Result<tokio::net::UnixListener, String> {
+    let path = socket_path();
+    if path.exists() {
+        // 能连上说明已有服务在运行，否则是上次异常退出留下的文件
+        if Stream::connect(&path).is_ok() {
+            return Err(format!("{:?} 已有服务在运行", path));
+        }
+        std::fs::remove_file(&path).map_err(|e| e.to_string())?;
+    }
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir).map_err(|e| e.to_string())?;
+    }
+    tokio::net::UnixListener::bind(&path).map_err(|e| e.to_string())
+}
+
+#[cfg(unix)]
+async fn accept_loop(listener: tokio::net::UnixListener, controller: Controller) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                tokio::spawn(handle_conn(stream, controller.clone()));
+            }
+            Err(e) => println!("IPC 连接失败: {}", e),
+        }
+    }
+}
+
+#[cfg(windows)]
+fn bind() -> Result<tokio::net::windows::named_pipe::NamedPipeServer, String> {
+    use tokio::net::windows::named_pipe::ServerOptions;
+    ServerOptions::new()
+        .first_pipe_instance(true)
+        .create(socket_path())
+        .map_err(|e| format!("{:?} 创建失败: {}", socket_path(), e))
+}
+
+// 每接入一个客户端就新建下一个管道实例
+#[cfg(windows)]
+async fn accept_loop(mut server: tokio::net::windows::named_pipe::NamedPipeServer, controller: Controller) {
+    use tokio::net::windows::named_pipe::ServerOptions;
+    loop {
+        if let Err(e) = server.connect().await {
+            println!("IPC 连接失败: {}", e);
+        }
+        let next = match ServerOptions::new().create(socket_path()) {
+            Ok(next) => next,
+            Err(e) => {
+                println!("IPC 管道创建失败: {}", e);
+                return;
+            }
+        };
+        let stream = std::mem::replace(&mut server, next);
+        tokio::spawn(handle_conn(stream, controller.clone()));
+    }
+}
+
+// 在后台线程提供 IPC 服务，控制循环需另外通过 Controller::spawn 启动
+pub fn start_server(controller: &Controller) -> Result<(), String> {
+    let rt = tokio::runtime::Builder::new_multi_thread()
+        .worker_threads(2)
+        .enable_all()
+        .build()
+        .map_err(|e| e.to_string())?;
+    let listener = {
+        let _guard = rt.enter();
+        bind()?
+    };
+    let controller = controller.clone();
+    thread::spawn(move || rt.block_on(accept_loop(listener, controller)));
+    println!("IPC 服务已启动: {:?}", socket_path());
+    Ok(())
+}
+
+// IPC 服务加上按配置启用的对外接口，守护进程和内置服务共用
+pub fn start_services(controller: &Controller) -> Result<(), String> {
+    start_server(controller)?;
+    let metrics = load_metrics_config();
+    if metrics.enabled {
+        // 对外接口启动失败不影响控制
+        if let Err(e) = start_metrics(controller, &metrics) {
+            println!("{}", e);
+        }
+    }
+    let mqtt = load_mqtt_config();
+    if mqtt.enabled {
+        if let Err(e) = start_mqtt(controller, &mqtt) {
+            println!("{}", e);
+        }
+    }
+    let rest = load_rest_config();
+    if rest.enabled {
+        if let Err(e) = start_rest(controller, &rest) {
+            println!("{}", e);
+        }
+    }
+    Ok(())
+}
+
+// 退出前删除套接字文件
+pub fn stop_server() {
+    #[cfg(unix)]
+    let _ = std::fs::remove_file(socket_path());
+}
+
+static EMBEDDED: Mutex<bool> = Mutex::new(false);
+
+// 没有守护进程时在本进程内启动控制循环和 IPC 服务，返回是否为内置服务
+pub fn ensure_server() -> Result<bool, String> {
+    let mut embedded = EMBEDDED.lock().unwrap();
+    if *embedded {
+        return Ok(true);
+    }
+    if IpcClient::connect().is_ok() {
+        return Ok(false);
+    }
+    // 只有内置服务访问 EC，界面进程连接守护进程时不初始化 WMI
+    ec_init();
+    device_init(ec_open().as_ref(), false)?;
+    restore_settings(ec_open().as_ref());
+    let controller = Controller::new();
+    start_services(&controller)?;
+    controller.spawn(|_| {});
+    *embedded = true;
+    println!("未检测到 nuc-fan-daemon，已启动内置服务");
+    Ok(true)
+}
+
+pub fn is_embedded() -> bool {
+    *EMBEDDED.lock().unwrap()
+}
+
+pub struct IpcClient {
+    reader: BufReader<Stream>,
+    writer: Stream,
+    next_id: u64,
+}
+
+impl IpcClient {
+    #[cfg(unix)]
+    fn open() -> std::io::Result<Stream> {
+        Stream::connect(socket_path())
+    }
+
+    // 所有管道实例都忙时稍后重试
+    #[cfg(windows)]
+    fn open() -> std::io::Result<Stream> {
+        const ERROR_PIPE_BUSY: i32 = 231;
+        let mut tries = 0;
+        loop {
+            match std::fs::OpenOptions::new().read(true).write(true).open(socket_path()) {
+                Err(e) if e.raw_os_error() == Some(ERROR_PIPE_BUSY) && tries < 20 => {
+                    tries += 1;
+                    thread::sleep(std::time::Duration::from_millis(50));
+                }
+                r => return r,
+            }
+        }
+    }
+
+    pub fn connect() -> Result<Self, String> {
+        let stream = Self::open().map_err(|e| format!("无法连接 {:?}: {}", socket_path(), e))?;
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(IpcClient { reader: BufReader::new(stream), writer, next_id: 0 })
+    }
+
+    fn read_message(&mut self) -> Result<Value, String> {
+        let mut line = String::new();
+        let n = self.reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        if n == 0 {
+            return Err("IPC 连接已断开".to_string());
+        }
+        serde_json::from_str(&line).map_err(|e| e.to_string())
+    }
+
+    pub fn call(&mut self, method: &str, params: Value) -> Result<Value, String> {
+        self.next_id += 1;
+        let request = json!({ "jsonrpc": "2.0", "id": self.next_id, "method": method, "params": params });
+        writeln!(self.writer, "{}", request).map_err(|e| e.to_string())?;
+        loop {
+            let message = self.read_message()?;
+            if message.get("id") != Some(&json!(self.next_id)) {
+                continue;
+            }
+            if let Some(error) = message.get("error") {
+                return Err(error.get("message").and_then(|m| m.as_str()).unwrap_or("未知错误").to_string());
+            }
+            return Ok(message.get("result").cloned().unwrap_or(Value::Null));
+        }
+    }
+
+    // 服务端接口版本不一致时拒绝使用
+    pub fn check_version(&mut self) -> Result<(), String> {
+        let version = self.call("version", Value::Null)?;
+        match version.get("api_version").and_then(|v| v.as_u64()) {
+            Some(API_VERSION) => Ok(()),
+            v => Err(format!("接口版本不兼容: 服务端 {:?}，客户端 {}", v, API_VERSION)),
+        }
+    }
+
+    // 阻塞接收推送，直到连接断开
+    pub fn subscribe<F>(mut self, topics: &[&str], mut on_message: F) -> Result<(), String>
+    where
+        F: FnMut(&str, Value),
+    {
+        self.call("subscribe", json!({ "topics": topics }))?;
+        loop {
+            let message = self.read_message()?;
+            if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
+                on_message(method, message.get("params").cloned().unwrap_or(Value::Null));
+            }
+        }
+    }
+}
+
+// 单次调用，每次新建连接
+pub fn rpc_call(method: &str, params: Value) -> Result<Value, String> {
+    IpcClient::connect()?.call(method, params)
+}
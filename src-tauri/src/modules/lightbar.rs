@@ -0,0 +1,126 @@
+use std::time::{Duration, Instant};
+use crate::modules::config::{load_lightbar_config, save_lightbar_config};
+use crate::modules::ec::{ec_read, ec_write_all, Ec};
+use crate::modules::struct_set::{LightbarState, Rgb, TelemetrySample, ThermalIndicator, ThermalSource};
+
+const ADDR_LIGHTBAR_CONTROL_BYTE: u16 = 1864;
+const ADDR_REDBAR_CONTROL_BYTE: u16 = 1865;
+const ADDR_GREENBAR_CONTROL_BYTE: u16 = 1866;
+const ADDR_BLUEBAR_CONTROL_BYTE: u16 = 1867;
+
+// 控制字节 0 为关，非 0 为开
+const LIGHTBAR_OFF: u8 = 0;
+const LIGHTBAR_ON: u8 = 1;
+
+// 颜色任一通道变化小于该值时不重写，避免温度在边界抖动时频繁写入
+const COLOR_DEADBAND: u8 = 8;
+
+pub fn read_lightbar(ec: &dyn Ec) -> LightbarState {
+    LightbarState {
+        on: ec_read(ec, ADDR_LIGHTBAR_CONTROL_BYTE) != LIGHTBAR_OFF as i64,
+        color: Rgb {
+            r: ec_read(ec, ADDR_REDBAR_CONTROL_BYTE) as u8,
+            g: ec_read(ec, ADDR_GREENBAR_CONTROL_BYTE) as u8,
+            b: ec_read(ec, ADDR_BLUEBAR_CONTROL_BYTE) as u8,
+        },
+    }
+}
+
+pub fn write_lightbar(ec: &dyn Ec, state: &LightbarState) -> Result<(), String> {
+    ec_write_all(ec, &[
+        (ADDR_REDBAR_CONTROL_BYTE, state.color.r),
+        (ADDR_GREENBAR_CONTROL_BYTE, state.color.g),
+        (ADDR_BLUEBAR_CONTROL_BYTE, state.color.b),
+        (ADDR_LIGHTBAR_CONTROL_BYTE, if state.on { LIGHTBAR_ON } else { LIGHTBAR_OFF }),
+    ])
+    .check()
+}
+
+// 手动设置会关闭温度指示，否则控制循环会覆盖
+pub fn set_lightbar(ec: &dyn Ec, state: &LightbarState) -> Result<(), String> {
+    write_lightbar(ec, state)?;
+    let mut config = load_lightbar_config();
+    config.manual = Some(*state);
+    config.thermal.enabled = false;
+    save_lightbar_config(&config)
+}
+
+pub fn thermal_config_check(thermal: &ThermalIndicator) -> Result<(), String> {
+    if thermal.low >= thermal.high {
+        return Err(format!("low 必须小于 high: {} {}", thermal.low, thermal.high));
+    }
+    if thermal.interval_ms < 100 {
+        return Err("interval_ms 不能小于 100".to_string());
+    }
+    Ok(())
+}
+
+// 温度指示由控制循环接管，只恢复手动设置
+pub fn apply_lightbar_config(ec: &dyn Ec) {
+    let config = load_lightbar_config();
+    if config.thermal.enabled {
+        return;
+    }
+    if let Some(state) = config.manual {
+        match write_lightbar(ec, &state) {
+            Ok(()) => println!("已恢复灯条设置"),
+            Err(e) => println!("lightbar_config.json: {}", e),
+        }
+    }
+}
+
+fn lerp(a: u8, b: u8, t: f64) -> u8 {
+    (a as f64 + (b as f64 - a as f64) * t).round() as u8
+}
+
+pub fn thermal_color(thermal: &ThermalIndicator, sample: &TelemetrySample) -> Rgb {
+    let value = match thermal.source {
+        ThermalSource::Temp => sample.cpu_temp.max(sample.gpu_temp),
+        ThermalSource::Duty => sample.left_duty.max(sample.right_duty),
+    };
+    let t = ((value - thermal.low) as f64 / (thermal.high - thermal.low) as f64).clamp(0.0, 1.0);
+    Rgb {
+        r: lerp(thermal.cold.r, thermal.hot.r, t),
+        g: lerp(thermal.cold.g, thermal.hot.g, t),
+        b: lerp(thermal.cold.b, thermal.hot.b, t),
+    }
+}
+
+fn close(a: Rgb, b: Rgb) -> bool {
+    a.r.abs_diff(b.r) < COLOR_DEADBAND && a.g.abs_diff(b.g) < COLOR_DEADBAND && a.b.abs_diff(b.b) < COLOR_DEADBAND
+}
+
+// 控制循环每次采样调用，按 interval_ms 限速且颜色变化明显时才写入 EC
+pub struct ThermalLightbar {
+    last: Option<(Instant, Rgb)>,
+}
+
+impl ThermalLightbar {
+    pub fn new() -> Self {
+        ThermalLightbar { last: None }
+    }
+
+    // 配置变化后重新写入
+    pub fn reset(&mut self) {
+        self.last = None;
+    }
+
+    pub fn update(&mut self, ec: &dyn Ec, thermal: &ThermalIndicator, sample: &TelemetrySample) {
+        let color = thermal_color(thermal, sample);
+        if let Some((at, last)) = self.last {
+            if at.elapsed() < Duration::from_millis(thermal.interval_ms) || close(color, last) {
+                return;
+            }
+        }
+        if let Err(e) = write_lightbar(ec, &LightbarState { on: true, color }) {
+            println!("灯条温度指示: {}", e);
+        }
+        self.last = Some((Instant::now(), color));
+    }
+}
+
+impl Default for ThermalLightbar {
+    fn default() -> Self {
+        ThermalLightbar::new()
+    }
+}
@@ -0,0 +1,118 @@
+use std::fmt::Write;
+use std::thread;
+use tokio::net::{TcpListener, TcpStream};
+use crate::modules::battery::read_battery;
+use crate::modules::control::Controller;
+use crate::modules::device::{has_feature, has_sensor, reg_1926_is_l1};
+use crate::modules::ec::{ec_error_count, ec_open, ec_thread_init};
+use crate::modules::http::{read_request, write_response};
+use crate::modules::struct_set::MetricsConfig;
+use crate::modules::tdp::read_power_limits;
+
+// 按 Prometheus 文本格式输出一个指标，labels 为空时不带标签
+fn metric(out: &mut String, name: &str, kind: &str, help: &str, values: &[(&str, f64)]) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} {}", name, kind);
+    for (labels, value) in values {
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", name, value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", name, labels, value);
+        }
+    }
+}
+
+// 温度、转速和占空比取控制循环最近一次采样，功率和电池在抓取时读取
+pub fn render_metrics(controller: &Controller) -> String {
+    let mut out = String::new();
+    let ec = ec_open();
+    if let Some(s) = controller.latest() {
+        let sample = &s.sample;
+        metric(&mut out, "nuc_fan_cpu_temp_celsius", "gauge", "CPU temperature.", &[("", sample.cpu_temp as f64)]);
+        metric(&mut out, "nuc_fan_gpu_temp_celsius", "gauge", "GPU temperature.", &[("", sample.gpu_temp as f64)]);
+        metric(&mut out, "nuc_fan_rpm", "gauge", "Fan speed in RPM.", &[
+            ("fan=\"left\"", sample.left_fan_speed as f64),
+            ("fan=\"right\"", sample.right_fan_speed as f64),
+        ]);
+        metric(&mut out, "nuc_fan_duty_percent", "gauge", "Commanded fan duty, 0 when the EC is in auto mode.", &[
+            ("fan=\"left\"", sample.left_duty as f64),
+            ("fan=\"right\"", sample.right_duty as f64),
+        ]);
+        metric(&mut out, "nuc_fan_control_running", "gauge", "1 when a fan curve is being applied.", &[("", s.running as u8 as f64)]);
+        metric(&mut out, "nuc_fan_tick_latency_seconds", "gauge", "Duration of the last control loop tick.", &[("", s.tick_ms / 1000.0)]);
+        metric(&mut out, "nuc_fan_apc_watts", "gauge", "Adapter power.", &[("", sample.apc_watt as f64)]);
+        let faults: Vec<String> = s.faults.iter().map(|f| format!("fault=\"{}\"", f)).collect();
+        let faults: Vec<(&str, f64)> = faults.iter().map(|f| (f.as_str(), 1.0)).collect();
+        metric(&mut out, "nuc_fan_fault", "gauge", "Active faults of the last tick.", &faults);
+    }
+    // 机型没有 TDP 寄存器时不输出功耗指标；1926 为固件风扇 L1 时不是 TCC 偏移
+    if has_feature("tdp") {
+        let limits = read_power_limits(ec.as_ref());
+        metric(&mut out, "nuc_fan_power_limit_watts", "gauge", "CPU power limits from the TDP registers.", &[
+            ("limit=\"pl1\"", limits.pl1 as f64),
+            ("limit=\"pl2\"", limits.pl2 as f64),
+            ("limit=\"pl4\"", limits.pl4 as f64),
+        ]);
+        metric(&mut out, "nuc_fan_gpu_power_limit", "gauge", "GPU power limit registers.", &[
+            ("limit=\"gpu1\"", limits.gpu1 as f64),
+            ("limit=\"gpu2\"", limits.gpu2 as f64),
+        ]);
+        if !reg_1926_is_l1() {
+            metric(&mut out, "nuc_fan_tcc_offset_celsius", "gauge", "TCC offset.", &[("", limits.tcc as f64)]);
+        }
+    }
+    // 机型没有电池传感器时不输出电池指标
+    if has_sensor("battery") {
+        let battery = read_battery(ec.as_ref());
+        metric(&mut out, "nuc_fan_battery_percent", "gauge", "Remaining battery charge.", &[("", battery.percent)]);
+        metric(&mut out, "nuc_fan_battery_health_percent", "gauge", "Full charge capacity relative to design capacity.", &[("", battery.health)]);
+        metric(&mut out, "nuc_fan_battery_energy_wh", "gauge", "Battery energy.", &[
+            ("kind=\"design\"", battery.design_capacity_mwh as f64 / 1000.0),
+            ("kind=\"full_charge\"", battery.full_charge_capacity_mwh as f64 / 1000.0),
+            ("kind=\"remaining\"", battery.remaining_capacity_mwh as f64 / 1000.0),
+        ]);
+        metric(&mut out, "nuc_fan_battery_voltage_volts", "gauge", "Battery voltage.", &[("", battery.voltage_mv as f64 / 1000.0)]);
+        metric(&mut out, "nuc_fan_battery_current_amperes", "gauge", "Battery current, negative when discharging.", &[("", battery.current_ma as f64 / 1000.0)]);
+        metric(&mut out, "nuc_fan_battery_cycles", "gauge", "Battery cycle count.", &[("", battery.cycle_count as f64)]);
+        let state = format!("state=\"{}\"", serde_json::to_value(battery.state).ok().and_then(|v| v.as_str().map(|s| s.to_string())).unwrap_or_default());
+        metric(&mut out, "nuc_fan_battery_state", "gauge", "Battery charging state.", &[(state.as_str(), 1.0)]);
+    }
+    metric(&mut out, "nuc_fan_ec_errors_total", "counter", "Failed WMI/EC calls since start.", &[("", ec_error_count() as f64)]);
+    out
+}
+
+async fn handle(mut stream: TcpStream, controller: Controller) {
+    let Ok(request) = read_request(&mut stream).await else { return };
+    let content_type = "text/plain; version=0.0.4; charset=utf-8";
+    if request.method != "GET" || request.path != "/metrics" {
+        write_response(&mut stream, "404 Not Found", content_type, &[], b"not found\n").await;
+        return;
+    }
+    let body = tokio::task::spawn_blocking(move || {
+        ec_thread_init();
+        render_metrics(&controller)
+    })
+    .await
+    .unwrap_or_default();
+    write_response(&mut stream, "200 OK", content_type, &[], body.as_bytes()).await;
+}
+
+// 在后台线程提供 /metrics
+pub fn start_metrics(controller: &Controller, config: &MetricsConfig) -> Result<(), String> {
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().map_err(|e| e.to_string())?;
+    let listener = rt
+        .block_on(TcpListener::bind(&config.bind))
+        .map_err(|e| format!("监听 {} 失败: {}", config.bind, e))?;
+    let controller = controller.clone();
+    thread::spawn(move || {
+        rt.block_on(async move {
+            loop {
+                if let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle(stream, controller.clone()));
+                }
+            }
+        })
+    });
+    println!("Prometheus 指标已启动: http://{}/metrics", config.bind);
+    Ok(())
+}
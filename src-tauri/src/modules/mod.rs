@@ -1,4 +1,30 @@
+#[cfg(feature = "gui")]
 pub mod setup;
+#[cfg(windows)]
 pub mod wmi;
 pub mod config;
-pub mod struct_set;
\ No newline at end of file
+pub mod struct_set;
+pub mod poll;
+pub mod telemetry;
+pub mod recorder;
+pub mod export;
+pub mod ec;
+pub mod sim;
+pub mod fan;
+pub mod tdp;
+pub mod profile;
+pub mod control;
+pub mod battery;
+pub mod rpc;
+pub mod ipc;
+pub mod metrics;
+pub mod mqtt;
+pub mod http;
+pub mod rest;
+pub mod charge;
+pub mod power;
+pub mod rgb;
+pub mod lightbar;
+pub mod vendor_fan;
+pub mod device;
+pub mod explorer;
\ No newline at end of file
@@ -0,0 +1,213 @@
+use std::thread;
+use std::time::{Duration, Instant};
+use rumqttc::{Client, Event, LastWill, MqttOptions, Packet, QoS};
+use serde_json::{json, Value};
+use tokio::sync::broadcast::error::RecvError;
+use crate::modules::control::Controller;
+use crate::modules::ec::ec_thread_init;
+use crate::modules::profile::list_profiles;
+use crate::modules::rpc::{dispatch, RpcError, INVALID_PARAMS};
+use crate::modules::struct_set::MqttConfig;
+
+// 传感器实体: 快照字段、名称、单位、device_class
+const SENSORS: [(&str, &str, &str, &str); 11] = [
+    ("cpu_temp", "CPU Temperature", "°C", "temperature"),
+    ("gpu_temp", "GPU Temperature", "°C", "temperature"),
+    ("left_fan_speed", "Left Fan Speed", "RPM", ""),
+    ("right_fan_speed", "Right Fan Speed", "RPM", ""),
+    ("left_duty", "Left Fan Duty", "%", ""),
+    ("right_duty", "Right Fan Duty", "%", ""),
+    ("apc_watt", "Adapter Power", "W", "power"),
+    ("cpu_pl1", "CPU PL1", "W", "power"),
+    ("cpu_pl2", "CPU PL2", "W", "power"),
+    ("battery_percent", "Battery", "%", "battery"),
+    ("battery_current", "Battery Current", "mA", "current"),
+];
+
+struct Topics {
+    state: String,
+    availability: String,
+    profile_set: String,
+    fan_set: String,
+}
+
+impl Topics {
+    fn new(config: &MqttConfig) -> Self {
+        let base = config.base_topic.trim_end_matches('/');
+        Topics {
+            state: format!("{}/state", base),
+            availability: format!("{}/availability", base),
+            profile_set: format!("{}/profile/set", base),
+            fan_set: format!("{}/fan/set", base),
+        }
+    }
+}
+
+// Home Assistant 自动发现配置，返回 (主题, 内容)
+fn discovery(config: &MqttConfig, topics: &Topics) -> Vec<(String, Value)> {
+    let id = &config.client_id;
+    let prefix = config.discovery_prefix.trim_end_matches('/');
+    let device = json!({
+        "identifiers": [id],
+        "name": "NUC X15",
+        "manufacturer": "Intel",
+        "model": "NUC X15",
+        "sw_version": env!("CARGO_PKG_VERSION"),
+    });
+    let mut entities = Vec::new();
+    for (field, name, unit, class) in SENSORS {
+        let mut payload = json!({
+            "name": name,
+            "unique_id": format!("{}_{}", id, field),
+            "state_topic": topics.state,
+            "value_template": format!("{{{{ value_json.{} }}}}", field),
+            "unit_of_measurement": unit,
+            "state_class": "measurement",
+            "availability_topic": topics.availability,
+            "device": device,
+        });
+        if !class.is_empty() {
+            payload["device_class"] = json!(class);
+        }
+        entities.push((format!("{}/sensor/{}/{}/config", prefix, id, field), payload));
+    }
+    let mut options = vec!["auto".to_string(), "custom".to_string()];
+    options.extend(list_profiles());
+    entities.push((format!("{}/select/{}/profile/config", prefix, id), json!({
+        "name": "Fan Profile",
+        "unique_id": format!("{}_profile", id),
+        "state_topic": topics.state,
+        "value_template": "{{ value_json.profile }}",
+        "command_topic": topics.profile_set,
+        "options": options,
+        "availability_topic": topics.availability,
+        "device": device,
+    })));
+    entities.push((format!("{}/binary_sensor/{}/running/config", prefix, id), json!({
+        "name": "Fan Curve Running",
+        "unique_id": format!("{}_running", id),
+        "state_topic": topics.state,
+        "value_template": "{{ 'ON' if value_json.running else 'OFF' }}",
+        "availability_topic": topics.availability,
+        "device": device,
+    })));
+    entities.push((format!("{}/button/{}/fan_auto/config", prefix, id), json!({
+        "name": "Fan Auto",
+        "unique_id": format!("{}_fan_auto", id),
+        "command_topic": topics.fan_set,
+        "payload_press": "auto",
+        "availability_topic": topics.availability,
+        "device": device,
+    })));
+    entities
+}
+
+// fan/set 支持 auto、"left,right" 和 {"left":..,"right":..}
+fn fan_params(payload: &str) -> Result<Value, RpcError> {
+    if let Ok(value @ Value::Object(_)) = serde_json::from_str::<Value>(payload) {
+        return Ok(value);
+    }
+    let parts: Vec<&str> = payload.split(',').map(|p| p.trim()).collect();
+    match parts.as_slice() {
+        [left, right] => match (left.parse::<i64>(), right.parse::<i64>()) {
+            (Ok(left), Ok(right)) => Ok(json!({ "left": left, "right": right })),
+            _ => Err(RpcError::new(INVALID_PARAMS, format!("无效的转速: {}", payload))),
+        },
+        _ => Err(RpcError::new(INVALID_PARAMS, format!("无效的转速: {}", payload))),
+    }
+}
+
+// 命令和 IPC 调用走同一套处理
+fn command(controller: &Controller, topics: &Topics, topic: &str, payload: &str) -> Result<Value, RpcError> {
+    let payload = payload.trim();
+    if topic == topics.profile_set {
+        if payload == "auto" {
+            return dispatch(controller, "fan.auto", &Value::Null);
+        }
+        return dispatch(controller, "profile.apply", &json!({ "name": payload }));
+    }
+    if topic == topics.fan_set {
+        if payload == "auto" {
+            return dispatch(controller, "fan.auto", &Value::Null);
+        }
+        return dispatch(controller, "fan.set", &fan_params(payload)?);
+    }
+    Err(RpcError::new(INVALID_PARAMS, format!("未知的主题: {}", topic)))
+}
+
+fn announce(client: &Client, config: &MqttConfig, topics: &Topics) {
+    for (topic, payload) in discovery(config, topics) {
+        let _ = client.try_publish(topic, QoS::AtLeastOnce, true, payload.to_string());
+    }
+    let _ = client.try_publish(&topics.availability, QoS::AtLeastOnce, true, "online");
+    let _ = client.try_subscribe(&topics.profile_set, QoS::AtLeastOnce);
+    let _ = client.try_subscribe(&topics.fan_set, QoS::AtLeastOnce);
+}
+
+// 连接断开后 rumqttc 会自动重连，重连成功时重新发布发现配置和订阅
+pub fn start_mqtt(controller: &Controller, config: &MqttConfig) -> Result<(), String> {
+    if config.client_id.is_empty() || config.base_topic.is_empty() {
+        return Err("MQTT client_id 和 base_topic 不能为空".to_string());
+    }
+    let topics = Topics::new(config);
+    let mut options = MqttOptions::new(&config.client_id, &config.host, config.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    options.set_last_will(LastWill::new(&topics.availability, "offline", QoS::AtLeastOnce, true));
+    if let Some(username) = &config.username {
+        options.set_credentials(username, config.password.clone().unwrap_or_default());
+    }
+    // 队列要能放下全部发现配置，接收线程自己发布时不会阻塞
+    let (client, mut connection) = Client::new(options, 64);
+
+    let receiver = client.clone();
+    let c = controller.clone();
+    let conf = config.clone();
+    thread::spawn(move || {
+        ec_thread_init();
+        let topics = Topics::new(&conf);
+        for event in connection.iter() {
+            match event {
+                Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                    println!("MQTT 已连接 {}:{}", conf.host, conf.port);
+                    announce(&receiver, &conf, &topics);
+                }
+                Ok(Event::Incoming(Packet::Publish(publish))) => {
+                    let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                    match command(&c, &topics, &publish.topic, &payload) {
+                        Ok(_) => println!("MQTT 命令 {} {}", publish.topic, payload),
+                        Err(e) => println!("MQTT 命令失败 {} {}: {}", publish.topic, payload, e.message),
+                    }
+                }
+                Err(e) => {
+                    println!("MQTT 连接失败: {}", e);
+                    thread::sleep(Duration::from_secs(5));
+                }
+                _ => {}
+            }
+        }
+    });
+
+    let mut updates = controller.sensor.updates.subscribe();
+    let interval = Duration::from_millis(config.publish_interval_ms);
+    let state = topics.state;
+    thread::spawn(move || {
+        let mut last: Option<Instant> = None;
+        loop {
+            match updates.blocking_recv() {
+                Ok(snapshot) => {
+                    if last.map(|t| t.elapsed() < interval).unwrap_or(false) {
+                        continue;
+                    }
+                    last = Some(Instant::now());
+                    let payload = serde_json::to_string(&snapshot).unwrap_or_default();
+                    // 断线时队列满了就丢弃，重连后发布最新状态
+                    let _ = client.try_publish(&state, QoS::AtMostOnce, false, payload);
+                }
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            }
+        }
+    });
+    println!("MQTT 已启用: {}:{}", config.host, config.port);
+    Ok(())
+}
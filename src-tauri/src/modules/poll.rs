@@ -0,0 +1,164 @@
+use std::time::{Duration, Instant};
+use crate::modules::struct_set::PollConfig;
+
+// 超过该温度直接满转
+pub const OVERHEAT_TEMP: i64 = 95;
+// 采样间隔上下限(ms)
+pub const MIN_INTERVAL_MS: u64 = 100;
+pub const MAX_INTERVAL_MS: u64 = 10000;
+
+pub fn poll_config_check(config: &PollConfig) -> Result<(), String> {
+    for (name, ms) in [
+        ("interval_ms", config.interval_ms),
+        ("fast_interval_ms", config.fast_interval_ms),
+        ("slow_interval_ms", config.slow_interval_ms),
+    ] {
+        if !(MIN_INTERVAL_MS..=MAX_INTERVAL_MS).contains(&ms) {
+            return Err(format!("{} 超出范围 {}~{}ms", name, MIN_INTERVAL_MS, MAX_INTERVAL_MS));
+        }
+    }
+    if config.fast_interval_ms > config.slow_interval_ms {
+        return Err("fast_interval_ms 不能大于 slow_interval_ms".to_string());
+    }
+    // 固定间隔模式不使用 fast/slow，不做限制
+    if config.adaptive && !(config.fast_interval_ms..=config.slow_interval_ms).contains(&config.interval_ms) {
+        return Err(format!(
+            "interval_ms 应在 fast_interval_ms~slow_interval_ms 之间 ({}~{}ms): {}",
+            config.fast_interval_ms, config.slow_interval_ms, config.interval_ms
+        ));
+    }
+    if config.rise_rate <= 0.0 {
+        return Err("rise_rate 必须大于0".to_string());
+    }
+    Ok(())
+}
+
+// 根据温度变化决定下一次采样间隔
+#[derive(Default)]
+pub struct PollScheduler {
+    last: Option<(Instant, i64)>,
+    stable_ticks: u32,
+}
+
+impl PollScheduler {
+    pub fn new() -> Self {
+        PollScheduler::default()
+    }
+
+    pub fn next_interval(&mut self, config: &PollConfig, cpu: i64, gpu: i64) -> Duration {
+        self.next_interval_at(config, cpu, gpu, Instant::now())
+    }
+
+    // now 由调用方给出，便于按模拟时间推进
+    pub fn next_interval_at(&mut self, config: &PollConfig, cpu: i64, gpu: i64, now: Instant) -> Duration {
+        let temp = cpu.max(gpu);
+        // 升温速率(℃/s)
+        let rate = match self.last {
+            Some((at, last)) => {
+                let secs = now.duration_since(at).as_secs_f64();
+                if secs > 0.0 { (temp - last) as f64 / secs } else { 0.0 }
+            }
+            None => 0.0,
+        };
+        // 温度波动不超过1℃视为稳定
+        if self.last.map(|(_, last)| (temp - last).abs() <= 1).unwrap_or(false) {
+            self.stable_ticks = self.stable_ticks.saturating_add(1);
+        } else {
+            self.stable_ticks = 0;
+        }
+        self.last = Some((now, temp));
+
+        if !config.adaptive {
+            return Duration::from_millis(config.interval_ms);
+        }
+        if rate >= config.rise_rate || temp >= OVERHEAT_TEMP - config.near_margin {
+            return Duration::from_millis(config.fast_interval_ms);
+        }
+        if temp < config.idle_temp && self.stable_ticks >= config.idle_ticks {
+            return Duration::from_millis(config.slow_interval_ms);
+        }
+        Duration::from_millis(config.interval_ms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sim::SimState;
+
+    const ADDR_CPU_TEMP: u16 = 0x43E;
+    const ADDR_GPU_TEMP: u16 = 0x44F;
+
+    fn adaptive() -> PollConfig {
+        PollConfig { adaptive: true, ..PollConfig::default() }
+    }
+
+    // 按 1s 步长推进模拟 EC，返回最后一次的采样间隔
+    fn run(sim: &mut SimState, scheduler: &mut PollScheduler, config: &PollConfig, start: Instant, from: u64, secs: u64) -> Duration {
+        let mut interval = Duration::ZERO;
+        for i in from..from + secs {
+            let now = start + Duration::from_secs(i + 1);
+            sim.advance(now);
+            let (cpu, gpu) = (sim.reg(ADDR_CPU_TEMP) as i64, sim.reg(ADDR_GPU_TEMP) as i64);
+            interval = scheduler.next_interval_at(config, cpu, gpu, now);
+        }
+        interval
+    }
+
+    #[test]
+    fn rising_temperature_polls_fast() {
+        let mut sim = SimState::new();
+        let start = Instant::now();
+        sim.set_load(1.0, 1.0);
+        let config = adaptive();
+        let interval = run(&mut sim, &mut PollScheduler::new(), &config, start, 0, 2);
+        assert_eq!(interval, Duration::from_millis(config.fast_interval_ms));
+    }
+
+    #[test]
+    fn near_threshold_polls_fast_when_stable() {
+        let mut sim = SimState::new();
+        let start = Instant::now();
+        sim.set_load(1.0, 1.0);
+        // 满载平衡点约 80℃，放宽 near_margin 让平衡点落在阈值附近
+        let config = PollConfig { near_margin: 20, ..adaptive() };
+        let mut scheduler = PollScheduler::new();
+        let interval = run(&mut sim, &mut scheduler, &config, start, 0, 120);
+        assert!(scheduler.stable_ticks > 0);
+        assert_eq!(interval, Duration::from_millis(config.fast_interval_ms));
+    }
+
+    #[test]
+    fn stable_idle_polls_slow() {
+        let mut sim = SimState::new();
+        let start = Instant::now();
+        sim.set_load(0.0, 0.0);
+        let config = adaptive();
+        let mut scheduler = PollScheduler::new();
+        // 降到环境温度前仍按默认间隔
+        assert_eq!(run(&mut sim, &mut scheduler, &config, start, 0, 1), Duration::from_millis(config.interval_ms));
+        let interval = run(&mut sim, &mut scheduler, &config, start, 1, 60);
+        assert_eq!(interval, Duration::from_millis(config.slow_interval_ms));
+    }
+
+    #[test]
+    fn fixed_interval_ignores_temperature() {
+        let mut sim = SimState::new();
+        let start = Instant::now();
+        sim.set_load(1.0, 1.0);
+        let config = PollConfig { interval_ms: 700, ..PollConfig::default() };
+        let mut scheduler = PollScheduler::new();
+        assert_eq!(run(&mut sim, &mut scheduler, &config, start, 0, 2), Duration::from_millis(700));
+        sim.set_load(0.0, 0.0);
+        assert_eq!(run(&mut sim, &mut scheduler, &config, start, 2, 60), Duration::from_millis(700));
+    }
+
+    #[test]
+    fn check_rejects_interval_outside_fast_slow() {
+        assert!(poll_config_check(&adaptive()).is_ok());
+        assert!(poll_config_check(&PollConfig { interval_ms: 200, ..adaptive() }).is_err());
+        assert!(poll_config_check(&PollConfig { interval_ms: 5000, ..adaptive() }).is_err());
+        // 固定间隔模式只检查上下限
+        assert!(poll_config_check(&PollConfig { interval_ms: 5000, ..PollConfig::default() }).is_ok());
+    }
+}
@@ -0,0 +1,206 @@
+use std::time::{Duration, Instant};
+use serde::{Deserialize, Serialize};
+use crate::modules::ec::{ec_read, Ec};
+use crate::modules::profile::list_profiles;
+use crate::modules::struct_set::{PowerConfig, PowerRule};
+use crate::modules::tdp::list_power_profiles;
+
+const ADDR_COMPLEX_POWER_STATUS: u16 = 1996;
+const ADDR_TYPEC_ADAPTER1_POWER: u16 = 1997;
+const ADDR_TYPEC_ADAPTER2_POWER: u16 = 1998;
+
+// 分析.md 只给出了地址，位含义按实测: bit0 圆口适配器，bit1 Type-C 适配器
+const STATUS_AC: u8 = 0x01;
+const STATUS_TYPEC: u8 = 0x02;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerSource {
+    #[default]
+    Battery,
+    Ac,
+    Usbc,
+}
+
+impl PowerSource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowerSource::Battery => "battery",
+            PowerSource::Ac => "ac",
+            PowerSource::Usbc => "usbc",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default, Debug)]
+pub struct PowerStatus {
+    pub source: PowerSource,
+    // ADDR_COMPLEX_POWER_STATUS 原始值
+    pub raw_status: u8,
+    // 两个 Type-C 口协商到的功率(W)，未接时为 0
+    pub typec_watt: [u8; 2],
+    // 当前适配器功率(W)，圆口适配器和电池供电时为 0
+    pub adapter_watt: u8,
+}
+
+// 圆口和 Type-C 同时接入时以圆口为准
+pub fn read_power_status(ec: &dyn Ec) -> PowerStatus {
+    let raw_status = ec_read(ec, ADDR_COMPLEX_POWER_STATUS) as u8;
+    let typec_watt = [
+        ec_read(ec, ADDR_TYPEC_ADAPTER1_POWER) as u8,
+        ec_read(ec, ADDR_TYPEC_ADAPTER2_POWER) as u8,
+    ];
+    let typec_max = typec_watt[0].max(typec_watt[1]);
+    let source = if raw_status & STATUS_AC != 0 {
+        PowerSource::Ac
+    } else if raw_status & STATUS_TYPEC != 0 || typec_max > 0 {
+        PowerSource::Usbc
+    } else {
+        PowerSource::Battery
+    };
+    PowerStatus {
+        source,
+        raw_status,
+        typec_watt,
+        adapter_watt: if source == PowerSource::Usbc { typec_max } else { 0 },
+    }
+}
+
+pub fn power_config_check(config: &PowerConfig) -> Result<(), String> {
+    let profiles = list_profiles();
+    let power_profiles = list_power_profiles();
+    for rule in &config.rules {
+        if rule.profile != "auto" && !profiles.contains(&rule.profile) {
+            return Err(format!("配置不存在: {}", rule.profile));
+        }
+        if let Some(name) = rule.power_profile.as_ref().filter(|n| !power_profiles.contains(n)) {
+            return Err(format!("功耗配置不存在: {}", name));
+        }
+    }
+    Ok(())
+}
+
+// 按顺序取第一条匹配的规则
+pub fn match_rule(rules: &[PowerRule], status: &PowerStatus) -> Option<usize> {
+    rules
+        .iter()
+        .position(|rule| rule.source == status.source && status.adapter_watt >= rule.min_watt.unwrap_or(0))
+}
+
+// 匹配结果持续 debounce 时长不变才切换，避免插拔抖动时来回切换配置
+pub struct PowerSwitcher {
+    pending: Option<(Option<usize>, Instant)>,
+    applied: Option<usize>,
+}
+
+impl PowerSwitcher {
+    pub fn new() -> Self {
+        PowerSwitcher { pending: None, applied: None }
+    }
+
+    // 返回需要应用的规则；没有规则匹配时保持当前配置
+    pub fn update(&mut self, matched: Option<usize>, debounce: Duration) -> Option<usize> {
+        self.update_at(matched, debounce, Instant::now())
+    }
+
+    // now 由调用方给出，便于按模拟时间推进
+    pub fn update_at(&mut self, matched: Option<usize>, debounce: Duration, now: Instant) -> Option<usize> {
+        if matched == self.applied {
+            self.pending = None;
+            return None;
+        }
+        match self.pending {
+            Some((m, since)) if m == matched => {
+                if now.saturating_duration_since(since) < debounce {
+                    return None;
+                }
+            }
+            _ => {
+                self.pending = Some((matched, now));
+                if !debounce.is_zero() {
+                    return None;
+                }
+            }
+        }
+        self.pending = None;
+        self.applied = matched;
+        matched
+    }
+}
+
+impl Default for PowerSwitcher {
+    fn default() -> Self {
+        PowerSwitcher::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(source: PowerSource, min_watt: Option<u8>, profile: &str) -> PowerRule {
+        PowerRule { source, min_watt, profile: profile.to_string(), tdp: None, power_profile: None }
+    }
+
+    fn status(source: PowerSource, adapter_watt: u8) -> PowerStatus {
+        PowerStatus { source, adapter_watt, ..PowerStatus::default() }
+    }
+
+    fn rules() -> Vec<PowerRule> {
+        vec![
+            rule(PowerSource::Usbc, Some(100), "usbc_fast"),
+            rule(PowerSource::Usbc, None, "usbc"),
+            rule(PowerSource::Ac, None, "ac"),
+            rule(PowerSource::Ac, None, "ac_shadowed"),
+            rule(PowerSource::Battery, None, "quiet"),
+        ]
+    }
+
+    #[test]
+    fn first_matching_rule_wins() {
+        let rules = rules();
+        assert_eq!(match_rule(&rules, &status(PowerSource::Ac, 0)), Some(2));
+        assert_eq!(match_rule(&rules, &status(PowerSource::Battery, 0)), Some(4));
+        assert_eq!(match_rule(&rules[..4], &status(PowerSource::Battery, 0)), None);
+    }
+
+    #[test]
+    fn typec_rule_needs_min_watt() {
+        let rules = rules();
+        assert_eq!(match_rule(&rules, &status(PowerSource::Usbc, 100)), Some(0));
+        assert_eq!(match_rule(&rules, &status(PowerSource::Usbc, 140)), Some(0));
+        assert_eq!(match_rule(&rules, &status(PowerSource::Usbc, 65)), Some(1));
+        assert_eq!(match_rule(&rules[..1], &status(PowerSource::Usbc, 99)), None);
+    }
+
+    #[test]
+    fn switches_only_after_source_is_stable() {
+        let debounce = Duration::from_millis(2000);
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut switcher = PowerSwitcher::new();
+        assert_eq!(switcher.update_at(Some(2), debounce, at(0)), None);
+        assert_eq!(switcher.update_at(Some(2), debounce, at(1999)), None);
+        assert_eq!(switcher.update_at(Some(2), debounce, at(2000)), Some(2));
+        // 已应用的规则不重复返回
+        assert_eq!(switcher.update_at(Some(2), debounce, at(5000)), None);
+
+        // 插拔抖动: 匹配结果变化时重新计时
+        assert_eq!(switcher.update_at(Some(4), debounce, at(6000)), None);
+        // 抖回已应用的规则时放弃等待中的切换
+        assert_eq!(switcher.update_at(Some(2), debounce, at(7000)), None);
+        assert_eq!(switcher.update_at(Some(4), debounce, at(7500)), None);
+        assert_eq!(switcher.update_at(Some(4), debounce, at(9000)), None);
+        assert_eq!(switcher.update_at(Some(4), debounce, at(9500)), Some(4));
+    }
+
+    #[test]
+    fn zero_debounce_switches_immediately() {
+        let mut switcher = PowerSwitcher::new();
+        let now = Instant::now();
+        assert_eq!(switcher.update_at(Some(1), Duration::ZERO, now), Some(1));
+        assert_eq!(switcher.update_at(Some(1), Duration::ZERO, now), None);
+        // 没有规则匹配时不切换
+        assert_eq!(switcher.update_at(None, Duration::ZERO, now), None);
+    }
+}
@@ -0,0 +1,55 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::modules::config::{get_config_dir, get_config_file_path};
+use crate::modules::struct_set::FanData;
+
+// 界面保存的配置即 default 曲线
+pub const DEFAULT_PROFILE: &str = "default";
+
+pub fn get_profile_dir() -> PathBuf {
+    get_config_dir().join("profiles")
+}
+
+pub fn check_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Err(format!("无效的配置名: {}", name));
+    }
+    Ok(())
+}
+
+fn profile_path(name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    if name == DEFAULT_PROFILE {
+        return get_config_file_path();
+    }
+    let dir = get_profile_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+pub fn list_profiles() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(get_profile_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_string_lossy().strip_suffix(".json").map(|n| n.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    if get_config_dir().join("fan_config.json").exists() {
+        names.insert(0, DEFAULT_PROFILE.to_string());
+    }
+    names
+}
+
+pub fn load_profile(name: &str) -> Result<FanData, String> {
+    let path = profile_path(name)?;
+    let json_data = fs::read_to_string(&path).map_err(|_| format!("配置不存在: {}", name))?;
+    serde_json::from_str(&json_data).map_err(|e| e.to_string())
+}
+
+pub fn save_profile(name: &str, fan_data: &FanData) -> Result<(), String> {
+    let json_data = serde_json::to_string_pretty(fan_data).map_err(|e| e.to_string())?;
+    fs::write(profile_path(name)?, json_data).map_err(|e| e.to_string())
+}
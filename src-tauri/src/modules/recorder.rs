@@ -0,0 +1,292 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use crate::modules::config::get_config_dir;
+use crate::modules::struct_set::{TelemetryConfig, TelemetryEvent, TelemetryRecord, TelemetrySample};
+
+const FILE_PREFIX: &str = "telemetry-";
+const FILE_EXT: &str = ".ndjson";
+
+pub fn get_telemetry_dir() -> PathBuf {
+    get_config_dir().join("telemetry")
+}
+
+// 按起始时间排序的日志文件列表
+fn log_files(dir: &Path) -> Vec<(u64, PathBuf)> {
+    let mut files: Vec<(u64, PathBuf)> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    let ts = name.strip_prefix(FILE_PREFIX)?.strip_suffix(FILE_EXT)?.parse::<u64>().ok()?;
+                    Some((ts, entry.path()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort_by_key(|(ts, _)| *ts);
+    files
+}
+
+fn record_ts(record: &TelemetryRecord) -> u64 {
+    match record {
+        TelemetryRecord::Sample(sample) => sample.ts,
+        TelemetryRecord::Event(event) => event.ts,
+    }
+}
+
+// 遥测数据按行写入 telemetry 目录，超过大小滚动新文件，按时长和总大小清理旧文件
+pub struct TelemetryRecorder {
+    dir: PathBuf,
+    config: TelemetryConfig,
+    file: Option<(PathBuf, File)>,
+    written: u64,
+}
+
+impl TelemetryRecorder {
+    pub fn new(config: TelemetryConfig) -> Self {
+        TelemetryRecorder::with_dir(get_telemetry_dir(), config)
+    }
+
+    pub fn with_dir(dir: PathBuf, config: TelemetryConfig) -> Self {
+        TelemetryRecorder {
+            dir,
+            config,
+            file: None,
+            written: 0,
+        }
+    }
+
+    pub fn set_config(&mut self, config: TelemetryConfig) {
+        if !config.record_to_disk {
+            self.file = None;
+        }
+        self.config = config;
+        self.prune();
+    }
+
+    pub fn record(&mut self, record: &TelemetryRecord) -> Result<(), String> {
+        if !self.config.record_to_disk {
+            return Ok(());
+        }
+        if self.file.is_none() || self.written >= self.config.max_file_kb.saturating_mul(1024) {
+            self.rotate(record_ts(record))?;
+        }
+        let mut line = serde_json::to_string(record).map_err(|e| e.to_string())?;
+        line.push('\n');
+        if let Some((_, file)) = self.file.as_mut() {
+            file.write_all(line.as_bytes()).map_err(|e| e.to_string())?;
+            self.written += line.len() as u64;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self, ts: u64) -> Result<(), String> {
+        fs::create_dir_all(&self.dir).map_err(|e| e.to_string())?;
+        let path = self.dir.join(format!("{}{}{}", FILE_PREFIX, ts, FILE_EXT));
+        let file = OpenOptions::new().create(true).append(true).open(&path).map_err(|e| e.to_string())?;
+        self.written = file.metadata().map(|m| m.len()).unwrap_or(0);
+        self.file = Some((path, file));
+        self.prune();
+        Ok(())
+    }
+
+    fn prune(&self) {
+        let current = self.file.as_ref().map(|(path, _)| path.clone());
+        let max_age = Duration::from_secs(self.config.max_age_hours.saturating_mul(3600));
+        let mut files: Vec<(PathBuf, u64)> = Vec::new();
+        for (_, path) in log_files(&self.dir) {
+            if Some(&path) == current.as_ref() {
+                continue;
+            }
+            let Ok(meta) = fs::metadata(&path) else { continue };
+            let expired = meta.modified().ok()
+                .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+                .map(|age| age > max_age)
+                .unwrap_or(false);
+            if expired {
+                let _ = fs::remove_file(&path);
+            } else {
+                files.push((path, meta.len()));
+            }
+        }
+        // 超出总大小时从最旧的文件开始删除
+        let max_total = self.config.max_total_mb.saturating_mul(1024 * 1024);
+        let mut total = files.iter().map(|(_, len)| len).sum::<u64>() + self.written;
+        for (path, len) in files {
+            if total <= max_total {
+                break;
+            }
+            if fs::remove_file(&path).is_ok() {
+                total -= len;
+            }
+        }
+    }
+
+    // 与 [start, end] 区间重叠的日志文件，只列目录不读内容
+    pub fn range_files(&self, start: u64, end: u64) -> Vec<PathBuf> {
+        let files = log_files(&self.dir);
+        files
+            .iter()
+            .enumerate()
+            .filter(|(i, (file_start, _))| {
+                // 文件覆盖的区间为 [本文件起始, 下一个文件起始)
+                let next_start = files.get(i + 1).map(|(ts, _)| *ts).unwrap_or(u64::MAX);
+                *file_start <= end && next_start >= start
+            })
+            .map(|(_, (_, path))| path.clone())
+            .collect()
+    }
+}
+
+// 读取文件中 [start, end] 区间内的记录，不需要持有记录器的锁
+pub fn read_records(files: &[PathBuf], start: u64, end: u64) -> (Vec<TelemetrySample>, Vec<TelemetryEvent>) {
+    let mut samples = Vec::new();
+    let mut events = Vec::new();
+    for path in files {
+        // 读取期间文件可能被清理，跳过即可
+        let Ok(file) = File::open(path) else { continue };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            match serde_json::from_str::<TelemetryRecord>(&line) {
+                Ok(TelemetryRecord::Sample(sample)) if sample.ts >= start && sample.ts <= end => samples.push(sample),
+                Ok(TelemetryRecord::Event(event)) if event.ts >= start && event.ts <= end => events.push(event),
+                _ => {}
+            }
+        }
+    }
+    (samples, events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("nuc-fan-recorder-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn config(max_file_kb: u64, max_total_mb: u64, max_age_hours: u64) -> TelemetryConfig {
+        TelemetryConfig { record_to_disk: true, max_file_kb, max_total_mb, max_age_hours, ..TelemetryConfig::default() }
+    }
+
+    fn sample(ts: u64) -> TelemetryRecord {
+        TelemetryRecord::Sample(TelemetrySample {
+            ts,
+            cpu_temp: 50,
+            gpu_temp: 40,
+            left_fan_speed: 3000,
+            right_fan_speed: 3100,
+            left_duty: 40,
+            right_duty: 40,
+            profile: "custom".to_string(),
+            apc_watt: 120,
+            cpu_pl1: 45,
+            cpu_pl2: 115,
+            battery_percent: 80,
+            battery_current: 0,
+        })
+    }
+
+    fn event(ts: u64, kind: &str) -> TelemetryRecord {
+        TelemetryRecord::Event(TelemetryEvent { ts, kind: kind.to_string(), detail: String::new() })
+    }
+
+    fn file_starts(dir: &Path) -> Vec<u64> {
+        log_files(dir).into_iter().map(|(ts, _)| ts).collect()
+    }
+
+    // 在目录中放一个指定大小的旧日志文件
+    fn put_file(dir: &Path, ts: u64, len: usize) -> PathBuf {
+        let path = dir.join(format!("{}{}{}", FILE_PREFIX, ts, FILE_EXT));
+        fs::write(&path, vec![b'\n'; len]).unwrap();
+        path
+    }
+
+    #[test]
+    fn rotates_when_file_exceeds_size() {
+        let dir = temp_dir("size");
+        let mut recorder = TelemetryRecorder::with_dir(dir.clone(), config(1, 64, 72));
+        for ts in 0..20 {
+            recorder.record(&sample(1000 + ts)).unwrap();
+        }
+        let files = log_files(&dir);
+        assert!(files.len() > 1, "{:?}", files);
+        // 新文件以滚动时那条记录的时间命名，只有最后一个文件可以不满
+        assert_eq!(files[0].0, 1000);
+        for (_, path) in &files[..files.len() - 1] {
+            assert!(fs::metadata(path).unwrap().len() >= 1024);
+        }
+        let (samples, _) = read_records(&files.iter().map(|(_, p)| p.clone()).collect::<Vec<_>>(), 0, u64::MAX);
+        assert_eq!(samples.len(), 20);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prunes_oldest_files_over_total_size() {
+        let dir = temp_dir("total");
+        for ts in [1, 2, 3] {
+            put_file(&dir, ts, 400 * 1024);
+        }
+        let mut recorder = TelemetryRecorder::with_dir(dir.clone(), config(1024, 1, 72));
+        recorder.record(&sample(10)).unwrap();
+        assert_eq!(file_starts(&dir), [2, 3, 10]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn prunes_files_older_than_max_age() {
+        let dir = temp_dir("age");
+        let old = put_file(&dir, 1, 10);
+        put_file(&dir, 2, 10);
+        let modified = SystemTime::now() - Duration::from_secs(2 * 3600);
+        File::options().write(true).open(&old).unwrap().set_modified(modified).unwrap();
+        let mut recorder = TelemetryRecorder::with_dir(dir.clone(), config(1024, 64, 72));
+        recorder.set_config(config(1024, 64, 3));
+        assert_eq!(file_starts(&dir), [1, 2]);
+        recorder.set_config(config(1024, 64, 1));
+        assert_eq!(file_starts(&dir), [2]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn range_files_select_overlapping_logs() {
+        let dir = temp_dir("range");
+        for ts in [1000, 2000, 3000] {
+            put_file(&dir, ts, 1);
+        }
+        let recorder = TelemetryRecorder::with_dir(dir.clone(), config(1024, 64, 72));
+        let starts = |start, end| -> Vec<String> {
+            recorder.range_files(start, end).iter().map(|p| p.file_name().unwrap().to_string_lossy().to_string()).collect()
+        };
+        assert!(starts(0, 500).is_empty());
+        assert_eq!(starts(1500, 1800), ["telemetry-1000.ndjson"]);
+        // 文件只记下起始时间，下一个文件起始处的记录可能还在上一个文件里
+        assert_eq!(starts(2000, 2500), ["telemetry-1000.ndjson", "telemetry-2000.ndjson"]);
+        assert_eq!(starts(3500, 9000), ["telemetry-3000.ndjson"]);
+        assert_eq!(starts(0, u64::MAX).len(), 3);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn read_records_filters_by_time() {
+        let dir = temp_dir("read");
+        let mut recorder = TelemetryRecorder::with_dir(dir.clone(), config(1024, 64, 72));
+        for ts in [1000, 2000, 3000] {
+            recorder.record(&sample(ts)).unwrap();
+        }
+        recorder.record(&event(2500, "stage")).unwrap();
+        let mut files: Vec<PathBuf> = log_files(&dir).into_iter().map(|(_, p)| p).collect();
+        // 无法解析的行和已被清理的文件直接跳过
+        OpenOptions::new().append(true).open(&files[0]).unwrap().write_all(b"not json\n").unwrap();
+        files.push(dir.join("telemetry-9999.ndjson"));
+        let (samples, events) = read_records(&files, 1500, 2500);
+        assert_eq!(samples.iter().map(|s| s.ts).collect::<Vec<_>>(), [2000]);
+        assert_eq!(events.iter().map(|e| e.ts).collect::<Vec<_>>(), [2500]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
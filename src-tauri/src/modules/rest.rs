@@ -0,0 +1,150 @@
+use std::thread;
+use serde_json::{json, Value};
+use tokio::net::{TcpListener, TcpStream};
+use crate::modules::config::save_rest_config;
+use crate::modules::control::Controller;
+use crate::modules::ec::ec_thread_init;
+use crate::modules::http::{read_request, write_response, HttpRequest};
+use crate::modules::rpc::{dispatch, RpcError, INVALID_PARAMS, METHOD_NOT_FOUND};
+use crate::modules::struct_set::RestConfig;
+
+const UNAUTHORIZED: i64 = -32001;
+
+// 系统随机源生成 256 位令牌，十六进制编码
+fn generate_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    getrandom::getrandom(&mut bytes).map_err(|e| format!("生成令牌失败: {}", e))?;
+    Ok(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 逐字节比较完整长度，耗时与不匹配位置无关
+fn token_matches(request: &HttpRequest, token: &str) -> bool {
+    let given = request.headers.get("authorization").and_then(|v| v.strip_prefix("Bearer ")).unwrap_or_default();
+    given.len() == token.len() && given.bytes().zip(token.bytes()).fold(0u8, |acc, (a, b)| acc | (a ^ b)) == 0
+}
+
+fn query_u64(request: &HttpRequest, key: &str) -> Result<Option<u64>, RpcError> {
+    match request.query.get(key) {
+        None => Ok(None),
+        Some(v) => v.parse::<u64>().map(Some).map_err(|_| RpcError::new(INVALID_PARAMS, format!("参数 {} 无效: {}", key, v))),
+    }
+}
+
+fn body_json(request: &HttpRequest) -> Result<Value, RpcError> {
+    serde_json::from_slice(&request.body).map_err(|e| RpcError::new(INVALID_PARAMS, format!("请求体不是有效的 JSON: {}", e)))
+}
+
+// 路由到对应的 RPC 方法，返回 (方法, 参数)
+fn route(request: &HttpRequest) -> Result<(&'static str, Value), RpcError> {
+    match (request.method.as_str(), request.path.trim_end_matches('/')) {
+        ("GET", "/api/v1/status") => Ok(("status", Value::Null)),
+        ("GET", "/api/v1/history") => Ok(("telemetry.query", json!({
+            "start": query_u64(request, "start")?,
+            "end": query_u64(request, "end")?,
+            "resolution_ms": query_u64(request, "resolution_ms")?,
+        }))),
+        ("GET", "/api/v1/profiles") => Ok(("profile.list", Value::Null)),
+        // {"name": "silent"}，name 为 auto 时恢复 EC 自动控制
+        ("POST", "/api/v1/profile") => {
+            let body = body_json(request)?;
+            match body.get("name").and_then(|n| n.as_str()) {
+                Some("auto") => Ok(("fan.auto", Value::Null)),
+                Some(_) => Ok(("profile.apply", body)),
+                None => Err(RpcError::new(INVALID_PARAMS, "缺少参数: name")),
+            }
+        }
+        // {"left": 40, "right": 50} 或 {"mode": "auto"}
+        ("POST", "/api/v1/fan") => {
+            let body = body_json(request)?;
+            if body.get("mode").and_then(|m| m.as_str()) == Some("auto") {
+                Ok(("fan.auto", Value::Null))
+            } else {
+                Ok(("fan.set", body))
+            }
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("未知的接口: {} {}", request.method, request.path))),
+    }
+}
+
+fn status_line(code: i64) -> &'static str {
+    match code {
+        UNAUTHORIZED => "401 Unauthorized",
+        INVALID_PARAMS => "400 Bad Request",
+        METHOD_NOT_FOUND => "404 Not Found",
+        _ => "500 Internal Server Error",
+    }
+}
+
+// 允许浏览器中的面板和插件跨域调用，鉴权仍依赖令牌
+const CORS: [(&str, &str); 3] = [
+    ("Access-Control-Allow-Origin", "*"),
+    ("Access-Control-Allow-Headers", "Authorization, Content-Type"),
+    ("Access-Control-Allow-Methods", "GET, POST, OPTIONS"),
+];
+
+async fn handle(mut stream: TcpStream, controller: Controller, token: String) {
+    let Ok(request) = read_request(&mut stream).await else { return };
+    if request.method == "OPTIONS" {
+        write_response(&mut stream, "204 No Content", "text/plain", &CORS, b"").await;
+        return;
+    }
+    let result = if !token_matches(&request, &token) {
+        Err(RpcError::new(UNAUTHORIZED, "令牌无效"))
+    } else {
+        match route(&request) {
+            Ok((method, params)) => tokio::task::spawn_blocking(move || {
+                ec_thread_init();
+                dispatch(&controller, method, &params)
+            })
+            .await
+            .unwrap_or_else(|e| Err(RpcError::from(e.to_string()))),
+            Err(e) => Err(e),
+        }
+    };
+    let (status, body) = match result {
+        Ok(value) => ("200 OK", value),
+        Err(e) => (status_line(e.code), json!({ "error": e.message })),
+    };
+    write_response(&mut stream, status, "application/json", &CORS, body.to_string().as_bytes()).await;
+}
+
+// 在后台线程提供 REST 接口，所有请求需带 Authorization: Bearer <token>
+pub fn start_rest(controller: &Controller, config: &RestConfig) -> Result<(), String> {
+    let mut config = config.clone();
+    if config.token.is_empty() {
+        config.token = generate_token()?;
+        save_rest_config(&config)?;
+        println!("已生成 REST 接口令牌，保存在 rest_config.json");
+    }
+    let addr = format!("{}:{}", config.bind, config.port);
+    let rt = tokio::runtime::Builder::new_current_thread().enable_all().build().map_err(|e| e.to_string())?;
+    let listener = rt
+        .block_on(TcpListener::bind(&addr))
+        .map_err(|e| format!("监听 {} 失败: {}", addr, e))?;
+    let controller = controller.clone();
+    let token = config.token;
+    thread::spawn(move || {
+        rt.block_on(async move {
+            loop {
+                if let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle(stream, controller.clone(), token.clone()));
+                }
+            }
+        })
+    });
+    println!("REST 接口已启动: http://{}/api/v1", addr);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_is_random_hex() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 64);
+        assert!(token.bytes().all(|b| b.is_ascii_hexdigit() && !b.is_ascii_uppercase()));
+        assert_ne!(token, generate_token().unwrap());
+    }
+}
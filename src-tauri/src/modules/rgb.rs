@@ -0,0 +1,129 @@
+use serde::{Deserialize, Serialize};
+use crate::modules::config::{load_rgb_config, save_rgb_config};
+use crate::modules::ec::{ec_read, ec_write, ec_write_all, Ec};
+use crate::modules::profile::check_name;
+use crate::modules::struct_set::{Rgb, RgbPreset};
+
+const ADDR_RGBKB_LEVEL_R: u16 = 1897;
+const ADDR_RGBKBDC_LEVEL_R: u16 = 2028;
+const ADDR_RGBKB_LEVEL_DEFAULT_R: u16 = 1900;
+// 亮度命令写入 ADDR_TRIGGER_BYTE，是命令寄存器，读回的不是写入值
+const ADDR_TRIGGER_BYTE: u16 = 1895;
+
+const RGBKBBKL_LEVEL_UPDATE: u8 = 240;
+const RGBKBBKL_LEVEL_DOWN: u8 = 177;
+const RGBKBBKL_LEVEL_UP: u8 = 178;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum RgbTarget {
+    Ac,
+    Battery,
+}
+
+impl RgbTarget {
+    // R/G/B 三个寄存器连续
+    fn addr(&self) -> u16 {
+        match self {
+            RgbTarget::Ac => ADDR_RGBKB_LEVEL_R,
+            RgbTarget::Battery => ADDR_RGBKBDC_LEVEL_R,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyboardRgb {
+    pub ac: Rgb,
+    pub battery: Rgb,
+    // EC 出厂颜色
+    pub default: Rgb,
+}
+
+fn read_rgb(ec: &dyn Ec, addr: u16) -> Rgb {
+    Rgb {
+        r: ec_read(ec, addr) as u8,
+        g: ec_read(ec, addr + 1) as u8,
+        b: ec_read(ec, addr + 2) as u8,
+    }
+}
+
+pub fn read_keyboard_rgb(ec: &dyn Ec) -> KeyboardRgb {
+    KeyboardRgb {
+        ac: read_rgb(ec, RgbTarget::Ac.addr()),
+        battery: read_rgb(ec, RgbTarget::Battery.addr()),
+        default: read_rgb(ec, ADDR_RGBKB_LEVEL_DEFAULT_R),
+    }
+}
+
+// 写入颜色后发送更新命令，EC 才会刷新键盘灯
+pub fn set_keyboard_rgb(ec: &dyn Ec, target: RgbTarget, rgb: Rgb) -> Result<(), String> {
+    let addr = target.addr();
+    ec_write_all(ec, &[(addr, rgb.r), (addr + 1, rgb.g), (addr + 2, rgb.b)]).check()?;
+    ec_write(ec, ADDR_TRIGGER_BYTE, RGBKBBKL_LEVEL_UPDATE);
+    Ok(())
+}
+
+pub fn step_keyboard_brightness(ec: &dyn Ec, up: bool) {
+    ec_write(ec, ADDR_TRIGGER_BYTE, if up { RGBKBBKL_LEVEL_UP } else { RGBKBBKL_LEVEL_DOWN });
+}
+
+// 接电源和用电池都恢复成出厂颜色，并清除启动时应用的预设
+pub fn restore_keyboard_rgb(ec: &dyn Ec) -> Result<Rgb, String> {
+    let default = read_rgb(ec, ADDR_RGBKB_LEVEL_DEFAULT_R);
+    set_keyboard_rgb(ec, RgbTarget::Ac, default)?;
+    set_keyboard_rgb(ec, RgbTarget::Battery, default)?;
+    let mut config = load_rgb_config();
+    config.active = None;
+    save_rgb_config(&config)?;
+    Ok(default)
+}
+
+pub fn list_rgb_presets() -> Vec<String> {
+    load_rgb_config().presets.into_keys().collect()
+}
+
+pub fn save_rgb_preset(name: &str, preset: &RgbPreset) -> Result<(), String> {
+    check_name(name)?;
+    let mut config = load_rgb_config();
+    config.presets.insert(name.to_string(), preset.clone());
+    save_rgb_config(&config)
+}
+
+pub fn delete_rgb_preset(name: &str) -> Result<(), String> {
+    let mut config = load_rgb_config();
+    if config.presets.remove(name).is_none() {
+        return Err(format!("键盘灯预设不存在: {}", name));
+    }
+    if config.active.as_deref() == Some(name) {
+        config.active = None;
+    }
+    save_rgb_config(&config)
+}
+
+fn write_preset(ec: &dyn Ec, preset: &RgbPreset) -> Result<(), String> {
+    set_keyboard_rgb(ec, RgbTarget::Ac, preset.ac)?;
+    set_keyboard_rgb(ec, RgbTarget::Battery, preset.battery)
+}
+
+// 应用后记为当前预设，下次启动时重新应用
+pub fn apply_rgb_preset(ec: &dyn Ec, name: &str) -> Result<RgbPreset, String> {
+    let mut config = load_rgb_config();
+    let preset = config.presets.get(name).cloned().ok_or_else(|| format!("键盘灯预设不存在: {}", name))?;
+    write_preset(ec, &preset)?;
+    config.active = Some(name.to_string());
+    save_rgb_config(&config)?;
+    Ok(preset)
+}
+
+pub fn apply_rgb_config(ec: &dyn Ec) {
+    let config = load_rgb_config();
+    let Some(name) = config.active else { return };
+    let written = match config.presets.get(&name) {
+        Some(preset) => write_preset(ec, preset),
+        None => Err(format!("键盘灯预设不存在: {}", name)),
+    };
+    match written {
+        Ok(()) => println!("已恢复键盘灯预设 {}", name),
+        Err(e) => println!("rgb_config.json: {}", e),
+    }
+}
@@ -0,0 +1,464 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use crate::modules::battery::{check_charge_limit, read_battery, set_charge_limit};
+use crate::modules::charge::{check_charge_schedule, read_charge_schedule, set_charge_schedule};
+use crate::modules::config::{
+    load_telemetry_config, resolve_in_dir, save_lightbar_config, save_poll_config, save_power_config, save_telemetry_config,
+};
+use crate::modules::control::Controller;
+use crate::modules::device::{current_device, require_feature};
+use crate::modules::ec::{ec_open, ec_read, ec_write, is_simulated, parse_addr};
+use crate::modules::explorer::{
+    default_snapshot_export_path, delete_snapshot, diff_live, diff_snapshots, dump_registers, export_snapshot, import_snapshot,
+    list_snapshots, load_snapshot, read_snapshot, register_labels, save_snapshot, RegisterRange,
+};
+use crate::modules::export::{check_columns, default_export_path, get_export_dir, write_export, ExportFormat};
+use crate::modules::fan::{fan_init, fan_mode_state, fan_reset, fan_set, read_fan_mode};
+use crate::modules::lightbar::{read_lightbar, set_lightbar, thermal_config_check, write_lightbar};
+use crate::modules::poll::poll_config_check;
+use crate::modules::power::{power_config_check, read_power_status};
+use crate::modules::profile::{list_profiles, load_profile, save_profile};
+use crate::modules::rgb::{
+    apply_rgb_preset, delete_rgb_preset, list_rgb_presets, read_keyboard_rgb, restore_keyboard_rgb, save_rgb_preset,
+    set_keyboard_rgb, step_keyboard_brightness, RgbTarget,
+};
+use crate::modules::struct_set::{
+    ChargeSchedule, FanData, LightbarState, NvOc, PollConfig, PowerConfig, PowerLimits, Rgb, RgbPreset, TelemetryConfig, Tdp,
+    ThermalIndicator, VendorFanSettings,
+};
+use crate::modules::tdp::{
+    check_nv_oc, check_power_limits, check_tdp, list_power_profiles, load_power_profile, read_mode_defaults, read_nv_oc,
+    read_power_limits, restore_mode_defaults, save_power_profile, write_nv_oc, write_power_limits, read_tdp, write_tdp,
+};
+use crate::modules::telemetry::{check_telemetry_config, downsample, now_ms};
+use crate::modules::vendor_fan::{
+    check_l1, check_vendor_fan, disable_vendor_fan, preview_fan_table, program_fan_table, read_vendor_fan, set_vendor_fan,
+};
+
+// 接口版本，方法或参数不兼容变更时加一
+pub const API_VERSION: u64 = 1;
+
+// JSON-RPC 2.0 错误码
+pub const PARSE_ERROR: i64 = -32700;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+pub const SERVER_ERROR: i64 = -32000;
+
+pub const METHODS: &[&str] = &[
+    "version", "status", "subscribe", "battery.get", "battery.set_charge_limit",
+    "battery.get_charge_schedule", "battery.set_charge_schedule",
+    "profile.list", "profile.get", "profile.save", "profile.apply", "profile.stop",
+    "fan.set", "fan.auto", "fan.mode", "vendor_fan.get", "vendor_fan.set", "vendor_fan.disable",
+    "vendor_fan.fit", "vendor_fan.program",
+    "tdp.get", "tdp.set",
+    "limits.get", "limits.set", "limits.defaults", "limits.restore", "nv_oc.get", "nv_oc.set",
+    "power_profile.list", "power_profile.get", "power_profile.save", "power_profile.apply",
+    "poll.get", "poll.set",
+    "power.get", "power.config.get", "power.config.set",
+    "rgb.get", "rgb.set", "rgb.brightness", "rgb.restore_default",
+    "rgb.preset.list", "rgb.preset.save", "rgb.preset.apply", "rgb.preset.delete",
+    "lightbar.get", "lightbar.set", "lightbar.thermal.get", "lightbar.thermal.set",
+    "telemetry.config.get", "telemetry.config.set", "telemetry.query", "telemetry.export",
+    "ec.read", "ec.write", "ec.dump", "ec.labels",
+    "ec.snapshot.take", "ec.snapshot.list", "ec.snapshot.get", "ec.snapshot.delete", "ec.snapshot.diff",
+    "ec.snapshot.export", "ec.snapshot.import",
+    "device.get", "methods", "ping",
+];
+
+#[derive(Debug)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into() }
+    }
+
+    pub fn to_value(&self) -> Value {
+        json!({ "code": self.code, "message": self.message })
+    }
+}
+
+impl From<String> for RpcError {
+    fn from(message: String) -> Self {
+        RpcError::new(SERVER_ERROR, message)
+    }
+}
+
+fn param<T: DeserializeOwned>(params: &Value, key: &str) -> Result<T, RpcError> {
+    let value = params.get(key).cloned().ok_or_else(|| RpcError::new(INVALID_PARAMS, format!("缺少参数: {}", key)))?;
+    serde_json::from_value(value).map_err(|e| RpcError::new(INVALID_PARAMS, format!("参数 {} 无效: {}", key, e)))
+}
+
+fn opt_param<T: DeserializeOwned>(params: &Value, key: &str) -> Result<Option<T>, RpcError> {
+    match params.get(key) {
+        None | Some(Value::Null) => Ok(None),
+        Some(_) => param(params, key).map(Some),
+    }
+}
+
+// 地址可以是数字，也可以是 "0x751" 这样的字符串
+fn addr_param(params: &Value, key: &str) -> Result<u16, RpcError> {
+    match params.get(key) {
+        Some(Value::String(s)) => Ok(parse_addr(s)?),
+        _ => param(params, key),
+    }
+}
+
+fn to_value<T: Serialize>(value: T) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(INTERNAL_ERROR, e.to_string()))
+}
+
+fn percent(params: &Value, key: &str) -> Result<i16, RpcError> {
+    let v: i16 = param(params, key)?;
+    if !(0..=100).contains(&v) {
+        return Err(RpcError::new(INVALID_PARAMS, format!("{} 超出范围 0~100: {}", key, v)));
+    }
+    Ok(v)
+}
+
+// 处理一次调用，会访问 EC，需在已调用 ec_thread_init 的阻塞线程中执行
+// subscribe 由连接层处理
+pub fn dispatch(controller: &Controller, method: &str, params: &Value) -> Result<Value, RpcError> {
+    // 方法前缀即功能名，机型不支持时拒绝
+    require_feature(method.split('.').next().unwrap_or(method))?;
+    match method {
+        "version" => Ok(json!({
+            "api_version": API_VERSION,
+            "version": env!("CARGO_PKG_VERSION"),
+            "simulated": is_simulated(),
+        })),
+        "ping" => Ok(json!("pong")),
+        "device.get" => to_value(current_device()),
+        "methods" => to_value(METHODS),
+        "status" => Ok(json!({
+            "running": controller.is_running(),
+            "profile": controller.fan.profile.lock().unwrap().clone(),
+            "snapshot": controller.latest(),
+        })),
+        "battery.get" => to_value(read_battery(ec_open().as_ref())),
+        "battery.set_charge_limit" => {
+            let limit: u8 = param(params, "limit")?;
+            check_charge_limit(limit).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            set_charge_limit(ec_open().as_ref(), limit)?;
+            println!("充电上限已设置为 {}%", limit);
+            Ok(json!({ "charge_limit": limit }))
+        }
+        "battery.get_charge_schedule" => to_value(read_charge_schedule(ec_open().as_ref())),
+        "battery.set_charge_schedule" => {
+            let schedule: ChargeSchedule = param(params, "schedule")?;
+            check_charge_schedule(&schedule).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            set_charge_schedule(ec_open().as_ref(), &schedule)?;
+            println!(
+                "充电速率已设置为 {}% / {}% ({}-{})",
+                schedule.first_rate, schedule.second_rate, schedule.second_start, schedule.second_end
+            );
+            to_value(schedule)
+        }
+        "profile.list" => to_value(list_profiles()),
+        "profile.get" => to_value(load_profile(&param::<String>(params, "name")?)?),
+        "profile.save" => {
+            let name: String = param(params, "name")?;
+            let fan_data: FanData = param(params, "fan_data")?;
+            save_profile(&name, &fan_data)?;
+            println!("风扇配置 {} 已保存", name);
+            Ok(Value::Null)
+        }
+        // 传入 fan_data 时直接运行该曲线，否则按名称加载已保存的配置
+        "profile.apply" => {
+            let name: String = param(params, "name")?;
+            let fan_data = match opt_param::<FanData>(params, "fan_data")? {
+                Some(fan_data) => fan_data,
+                None => load_profile(&name)?,
+            };
+            controller.start(ec_open().as_ref(), &name, to_value(fan_data)?);
+            Ok(json!({ "profile": name }))
+        }
+        "profile.stop" => {
+            controller.stop();
+            fan_reset(ec_open().as_ref());
+            Ok(Value::Null)
+        }
+        // 固定转速会先停止曲线控制
+        "fan.set" => {
+            let (left, right) = (percent(params, "left")?, percent(params, "right")?);
+            controller.stop();
+            let ec = ec_open();
+            fan_init(ec.as_ref());
+            let report = fan_set(ec.as_ref(), left, right)?;
+            controller.telemetry.record_event("override", format!("{} {}", left, right));
+            Ok(json!({ "left": left, "right": right, "ok": report.ok, "writes": report.writes }))
+        }
+        "fan.auto" => {
+            controller.stop();
+            fan_reset(ec_open().as_ref());
+            Ok(json!({ "mode": "auto" }))
+        }
+        "fan.mode" => Ok(json!({
+            "mode": read_fan_mode(ec_open().as_ref()),
+            "state": fan_mode_state(),
+        })),
+        "vendor_fan.get" => to_value(read_vendor_fan(ec_open().as_ref())),
+        // 固件风扇需要 EC 自动控制，先停止曲线调速
+        "vendor_fan.set" => {
+            let settings: VendorFanSettings = param(params, "settings")?;
+            check_vendor_fan(&settings).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            check_l1(ec_open().as_ref(), &settings).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            controller.stop();
+            set_vendor_fan(ec_open().as_ref(), &settings)?;
+            controller.telemetry.record_event("vendor_fan", format!("mode {} {:?}", settings.mode, settings.curve));
+            println!("已切换到固件风扇模式 {}", settings.mode);
+            to_value(settings)
+        }
+        "vendor_fan.fit" => {
+            let name: String = param(params, "name")?;
+            let mode: u8 = param(params, "mode")?;
+            to_value(preview_fan_table(ec_open().as_ref(), &name, mode).map_err(|e| RpcError::new(INVALID_PARAMS, e))?)
+        }
+        "vendor_fan.program" => {
+            let name: String = param(params, "name")?;
+            let mode: u8 = param(params, "mode")?;
+            let ec = ec_open();
+            preview_fan_table(ec.as_ref(), &name, mode).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            controller.stop();
+            let fit = program_fan_table(ec.as_ref(), &name, mode)?;
+            controller.telemetry.record_event("vendor_fan", format!("profile {} -> {:?}", name, fit.settings.curve));
+            to_value(fit)
+        }
+        "vendor_fan.disable" => {
+            disable_vendor_fan(ec_open().as_ref())?;
+            println!("已关闭固件风扇模式");
+            Ok(Value::Null)
+        }
+        "tdp.get" => to_value(read_tdp(ec_open().as_ref())),
+        "tdp.set" => {
+            let tdp: Tdp = param(params, "tdp")?;
+            check_tdp(&tdp).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            to_value(write_tdp(ec_open().as_ref(), &tdp)?)
+        }
+        "limits.get" => to_value(read_power_limits(ec_open().as_ref())),
+        "limits.set" => {
+            let limits: PowerLimits = param(params, "limits")?;
+            check_power_limits(&limits).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            to_value(write_power_limits(ec_open().as_ref(), &limits)?)
+        }
+        "limits.defaults" => to_value(read_mode_defaults(ec_open().as_ref())),
+        "limits.restore" => {
+            let mode: u8 = param(params, "mode")?;
+            let limits = restore_mode_defaults(ec_open().as_ref(), mode).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            println!("已恢复模式 {} 的出厂功耗设置", mode);
+            to_value(limits)
+        }
+        "nv_oc.get" => to_value(read_nv_oc(ec_open().as_ref())),
+        "nv_oc.set" => {
+            let oc: NvOc = param(params, "config")?;
+            check_nv_oc(&oc).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            to_value(write_nv_oc(ec_open().as_ref(), &oc)?)
+        }
+        "power_profile.list" => to_value(list_power_profiles()),
+        "power_profile.get" => to_value(load_power_profile(&param::<String>(params, "name")?)?),
+        // 不传 limits 时保存 EC 当前的设置
+        "power_profile.save" => {
+            let name: String = param(params, "name")?;
+            let limits = match opt_param::<PowerLimits>(params, "limits")? {
+                Some(limits) => limits,
+                None => read_power_limits(ec_open().as_ref()),
+            };
+            save_power_profile(&name, &limits).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            println!("功耗配置 {} 已保存", name);
+            to_value(limits)
+        }
+        "power_profile.apply" => {
+            let name: String = param(params, "name")?;
+            let limits = load_power_profile(&name)?;
+            let report = write_power_limits(ec_open().as_ref(), &limits)?;
+            if report.ok {
+                println!("已应用功耗配置 {}", name);
+            }
+            to_value(report)
+        }
+        "poll.get" => to_value(controller.poll.config.lock().unwrap().clone()),
+        "poll.set" => {
+            let config: PollConfig = param(params, "config")?;
+            poll_config_check(&config)?;
+            save_poll_config(&config)?;
+            // 控制线程下一次采样时生效
+            *controller.poll.config.lock().unwrap() = config;
+            println!("采样配置已更新");
+            Ok(Value::Null)
+        }
+        "power.get" => to_value(read_power_status(ec_open().as_ref())),
+        "power.config.get" => to_value(controller.power.config.lock().unwrap().clone()),
+        "power.config.set" => {
+            let config: PowerConfig = param(params, "config")?;
+            power_config_check(&config).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            save_power_config(&config)?;
+            // 控制线程下一次采样时按新规则重新匹配
+            *controller.power.config.lock().unwrap() = config;
+            println!("供电切换规则已更新");
+            Ok(Value::Null)
+        }
+        "rgb.get" => to_value(read_keyboard_rgb(ec_open().as_ref())),
+        "rgb.set" => {
+            let target: RgbTarget = param(params, "target")?;
+            let color: Rgb = param(params, "color")?;
+            set_keyboard_rgb(ec_open().as_ref(), target, color)?;
+            to_value(color)
+        }
+        "rgb.brightness" => {
+            let up = match param::<String>(params, "direction")?.as_str() {
+                "up" => true,
+                "down" => false,
+                other => return Err(RpcError::new(INVALID_PARAMS, format!("direction 只能是 up 或 down: {}", other))),
+            };
+            step_keyboard_brightness(ec_open().as_ref(), up);
+            Ok(Value::Null)
+        }
+        "rgb.restore_default" => to_value(restore_keyboard_rgb(ec_open().as_ref())?),
+        "rgb.preset.list" => to_value(list_rgb_presets()),
+        // 不传 preset 时保存 EC 当前的颜色
+        "rgb.preset.save" => {
+            let name: String = param(params, "name")?;
+            let preset = match opt_param::<RgbPreset>(params, "preset")? {
+                Some(preset) => preset,
+                None => {
+                    let rgb = read_keyboard_rgb(ec_open().as_ref());
+                    RgbPreset { ac: rgb.ac, battery: rgb.battery }
+                }
+            };
+            save_rgb_preset(&name, &preset).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            println!("键盘灯预设 {} 已保存", name);
+            to_value(preset)
+        }
+        "rgb.preset.apply" => {
+            let name: String = param(params, "name")?;
+            let preset = apply_rgb_preset(ec_open().as_ref(), &name)?;
+            println!("已应用键盘灯预设 {}", name);
+            to_value(preset)
+        }
+        "rgb.preset.delete" => {
+            let name: String = param(params, "name")?;
+            delete_rgb_preset(&name).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            Ok(Value::Null)
+        }
+        "lightbar.get" => to_value(read_lightbar(ec_open().as_ref())),
+        "lightbar.set" => {
+            let state = LightbarState { on: param(params, "on")?, color: opt_param(params, "color")?.unwrap_or_default() };
+            let mut config = controller.lightbar.config.lock().unwrap();
+            set_lightbar(ec_open().as_ref(), &state)?;
+            config.manual = Some(state);
+            config.thermal.enabled = false;
+            to_value(state)
+        }
+        "lightbar.thermal.get" => to_value(controller.lightbar.config.lock().unwrap().thermal.clone()),
+        // 关闭温度指示时恢复手动设置
+        "lightbar.thermal.set" => {
+            let thermal: ThermalIndicator = param(params, "config")?;
+            thermal_config_check(&thermal).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let mut config = controller.lightbar.config.lock().unwrap();
+            config.thermal = thermal;
+            save_lightbar_config(&config)?;
+            if let (false, Some(state)) = (config.thermal.enabled, config.manual) {
+                write_lightbar(ec_open().as_ref(), &state)?;
+            }
+            println!("灯条温度指示已{}", if config.thermal.enabled { "开启" } else { "关闭" });
+            Ok(Value::Null)
+        }
+        "telemetry.config.get" => to_value(load_telemetry_config()),
+        "telemetry.config.set" => {
+            let config: TelemetryConfig = param(params, "config")?;
+            check_telemetry_config(&config).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            save_telemetry_config(&config)?;
+            let telemetry = &controller.telemetry;
+            telemetry.buffer.lock().unwrap().set_retention(config.retention_secs);
+            telemetry.recorder.lock().unwrap().set_config(config);
+            Ok(Value::Null)
+        }
+        "telemetry.query" => {
+            // 默认返回最近10分钟的原始采样
+            let end = opt_param(params, "end")?.unwrap_or_else(now_ms);
+            let start = opt_param(params, "start")?.unwrap_or(end.saturating_sub(600 * 1000));
+            let (samples, _) = controller.telemetry.history(start, end);
+            to_value(downsample(&samples, start, opt_param(params, "resolution_ms")?.unwrap_or(0)))
+        }
+        "telemetry.export" => {
+            let start: u64 = param(params, "start")?;
+            let end = opt_param(params, "end")?.unwrap_or_else(now_ms);
+            let format = ExportFormat::parse(&param::<String>(params, "format")?)?;
+            let columns = check_columns(opt_param(params, "columns")?)?;
+            // 只能写到 exports 目录下
+            let path = match opt_param::<String>(params, "path")? {
+                Some(name) => resolve_in_dir(&get_export_dir(), &name).map_err(|e| RpcError::new(INVALID_PARAMS, e))?,
+                None => default_export_path(&format, end)?,
+            };
+            let (samples, events) = controller.telemetry.history(start, end);
+            let rows = write_export(&samples, &events, &columns, &format, &path)?;
+            println!("已导出 {} 条记录到 {:?}", rows, &path);
+            Ok(json!({ "path": path.to_string_lossy(), "rows": rows }))
+        }
+        "ec.read" => {
+            let addr = addr_param(params, "addr")?;
+            Ok(json!({ "addr": addr, "value": ec_read(ec_open().as_ref(), addr) }))
+        }
+        "ec.write" => {
+            let addr = addr_param(params, "addr")?;
+            let value: u8 = param(params, "value")?;
+            let ec = ec_open();
+            let out = ec_write(ec.as_ref(), addr, value);
+            // 原样写入不重试，命令类寄存器读回值可能和写入值不同
+            let read_back = ec_read(ec.as_ref(), addr);
+            Ok(json!({ "addr": addr, "value": value, "return": out, "read_back": read_back }))
+        }
+        "ec.dump" => {
+            let range = RegisterRange { start: addr_param(params, "start")?, len: param(params, "len")? };
+            to_value(dump_registers(ec_open().as_ref(), &range).map_err(|e| RpcError::new(INVALID_PARAMS, e))?)
+        }
+        "ec.labels" => to_value(register_labels()),
+        // 未给出 ranges 时记录寄存器表中的全部地址
+        "ec.snapshot.take" => {
+            let name: String = param(params, "name")?;
+            let ranges: Vec<RegisterRange> = opt_param(params, "ranges")?.unwrap_or_default();
+            let snapshot = read_snapshot(ec_open().as_ref(), &name, &ranges).map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            save_snapshot(&snapshot)?;
+            println!("已保存 EC 快照 {}，共 {} 个寄存器", name, snapshot.registers.len());
+            to_value(snapshot)
+        }
+        "ec.snapshot.list" => to_value(list_snapshots()),
+        "ec.snapshot.get" => to_value(load_snapshot(&param::<String>(params, "name")?)?),
+        "ec.snapshot.delete" => {
+            delete_snapshot(&param::<String>(params, "name")?)?;
+            Ok(Value::Null)
+        }
+        // 未给出 to 时和 EC 当前值比较
+        "ec.snapshot.diff" => {
+            let from = load_snapshot(&param::<String>(params, "from")?)?;
+            match opt_param::<String>(params, "to")? {
+                Some(to) => to_value(diff_snapshots(&from, &load_snapshot(&to)?)),
+                None => to_value(diff_live(ec_open().as_ref(), &from)),
+            }
+        }
+        "ec.snapshot.export" => {
+            let name: String = param(params, "name")?;
+            // 只能写到 exports 目录下
+            let path = match opt_param::<String>(params, "path")? {
+                Some(name) => resolve_in_dir(&get_export_dir(), &name).map_err(|e| RpcError::new(INVALID_PARAMS, e))?,
+                None => default_snapshot_export_path(&name)?,
+            };
+            export_snapshot(&name, &path)?;
+            println!("已导出 EC 快照 {} 到 {:?}", name, &path);
+            Ok(json!({ "path": path.to_string_lossy() }))
+        }
+        "ec.snapshot.import" => {
+            // 只能读 exports 目录下的文件
+            let path = resolve_in_dir(&get_export_dir(), &param::<String>(params, "path")?)
+                .map_err(|e| RpcError::new(INVALID_PARAMS, e))?;
+            let name: Option<String> = opt_param(params, "name")?;
+            to_value(import_snapshot(&path, name.as_deref())?)
+        }
+        _ => Err(RpcError::new(METHOD_NOT_FOUND, format!("未知的方法: {}", method))),
+    }
+}
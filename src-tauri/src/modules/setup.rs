@@ -5,7 +5,8 @@ use tauri::menu::{MenuBuilder, MenuItemBuilder};
 use tauri::plugin::PermissionState;
 use tauri::tray::{MouseButton, MouseButtonState, TrayIconEvent, TrayIconBuilder};
 use tauri_plugin_notification::NotificationExt;
-use crate::{fan_reset, get_config_dir};
+use serde_json::Value;
+use crate::modules::{config::get_config_dir, ipc::{is_embedded, rpc_call}};
 
 pub fn init(app: &mut App) -> Result<(), Box<dyn Error>> {
     let config_tdp = get_config_dir().join("debug.config");
@@ -52,7 +53,10 @@ pub fn init(app: &mut App) -> Result<(), Box<dyn Error>> {
                 }
                 thread::spawn(move || {
                     thread::sleep(Duration::from_secs(1));
-                    fan_reset();
+                    // 守护进程继续运行，只有内置服务随界面退出时恢复自动控制
+                    if is_embedded() {
+                        let _ = rpc_call("fan.auto", Value::Null);
+                    }
                     println!("退出");
                     process::exit(0);
                 });
@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Instant;
+use crate::modules::ec::Ec;
+
+// 风扇控制模式寄存器，读出值高字节固定为 0x6C
+const ADDR_FAN_MODE: u16 = 0x751;
+const FAN_MODE_HIGH: i64 = 0x6C00;
+const FAN_MODE_AUTO: u8 = 0x10;
+const FAN_MODE_MANUAL: u8 = 0x40;
+const FAN_MODE_RESET: u8 = 0xA0;
+// 手动转速，写入值为百分比 * 2
+const ADDR_LEFT_DUTY: u16 = 0x1809;
+const ADDR_RIGHT_DUTY: u16 = 0x1804;
+const ADDR_CPU_TEMP: u16 = 0x43E;
+const ADDR_GPU_TEMP: u16 = 0x44F;
+const ADDR_LEFT_RPM: u16 = 0x46C;
+const ADDR_RIGHT_RPM: u16 = 0x464;
+
+const AMBIENT: f64 = 35.0;
+const RPM_PER_DUTY: f64 = 55.0;
+// 温度向平衡点收敛的时间常数(s)
+const TIME_CONSTANT: f64 = 5.0;
+
+// 模拟 EC：寄存器表加一个简单的散热模型，用于在没有硬件时运行守护进程和 CLI
+pub struct SimState {
+    regs: HashMap<u16, u8>,
+    cpu_temp: f64,
+    gpu_temp: f64,
+    // 负载 0~1，为 None 时按时间缓慢波动
+    load: Option<(f64, f64)>,
+    // 模拟 EC 卡在某个风扇模式，写 0x751 不生效
+    fan_mode_stuck: Option<u8>,
+    started: Instant,
+    last: Instant,
+}
+
+impl SimState {
+    pub fn new() -> Self {
+        let now = Instant::now();
+        let mut regs = HashMap::new();
+        regs.insert(ADDR_FAN_MODE, FAN_MODE_AUTO);
+        // 机型识别: 与 devices.json 中 X15 条目的 project_id 和 BIOS 信息一致
+        regs.insert(1856, 113);
+        regs.insert(1126, 21);
+        // TDP 寄存器默认值
+        regs.insert(0x783, 45);
+        regs.insert(0x784, 115);
+        regs.insert(0x786, 0);
+        regs.insert(0x73d, 0);
+        regs.insert(0x733, 0);
+        regs.insert(0x785, 135);
+        // PSYS PL1 230W
+        regs.insert(1824, 0);
+        regs.insert(1825, 230);
+        // 显卡超频关闭，CTGP 100W，CPU 功耗目标 45W，最大 TGP 125W
+        regs.insert(1859, 0);
+        regs.insert(1860, 100);
+        regs.insert(1861, 45);
+        regs.insert(1862, 125);
+        // 固件风扇: 未启用，Profile1~3，五档 PWM(L1 与 TCC 偏移同地址)，MYFANI
+        let vendor_fan = [
+            (1968u16, 1u8), (1969, 2), (1970, 3),
+            (1927, 60), (1928, 100), (1929, 150), (1930, 200),
+            (1950, 30), (1951, 45), (1952, 10),
+        ];
+        for (addr, value) in vendor_fan {
+            regs.insert(addr, value);
+        }
+        // 各模式出厂默认值: PL1、PL2、PL4、DState
+        for (addr, values) in [(1840u16, [45u8, 115, 135, 0]), (1844, [35, 90, 120, 0]), (1959, [25, 40, 60, 0])] {
+            for (i, value) in values.into_iter().enumerate() {
+                regs.insert(addr + i as u16, value);
+            }
+        }
+        // 适配器功率
+        regs.insert(0x44C, 180);
+        // 电池: 容量单位 mAh，设计 6100，满充 5800，剩余 4300，电压 15.8V，循环 42 次
+        regs.insert(1024, 1);
+        // 充电上限
+        regs.insert(1977, 100);
+        // 键盘灯: 接电源、电池和出厂颜色
+        for addr in [1897u16, 2028, 1900] {
+            regs.insert(addr, 255);
+            regs.insert(addr + 1, 255);
+            regs.insert(addr + 2, 255);
+        }
+        // 供电: 圆口适配器，Type-C 未接
+        regs.insert(1996, 1);
+        // 双速率充电: 全天 100%，第二速率时间段为空
+        regs.insert(2016, 100);
+        regs.insert(2017, 100);
+        for (addr, value) in [(1026u16, 6100u16), (1028, 5800), (1078, 4300), (1032, 15400), (1080, 15800), (1190, 42)] {
+            regs.insert(addr, (value >> 8) as u8);
+            regs.insert(addr + 1, (value & 0xFF) as u8);
+        }
+        let mut state = SimState {
+            regs,
+            cpu_temp: AMBIENT + 10.0,
+            gpu_temp: AMBIENT + 5.0,
+            load: None,
+            fan_mode_stuck: None,
+            started: now,
+            last: now,
+        };
+        state.update_sensors();
+        state
+    }
+
+    pub fn set_load(&mut self, cpu: f64, gpu: f64) {
+        self.load = Some((cpu.clamp(0.0, 1.0), gpu.clamp(0.0, 1.0)));
+    }
+
+    pub fn set_fan_mode_stuck(&mut self, mode: Option<u8>) {
+        self.fan_mode_stuck = mode;
+        if let Some(mode) = mode {
+            self.set_reg(ADDR_FAN_MODE, mode);
+        }
+    }
+
+    pub fn reg(&self, addr: u16) -> u8 {
+        self.regs.get(&addr).copied().unwrap_or(0)
+    }
+
+    pub fn set_reg(&mut self, addr: u16, value: u8) {
+        self.regs.insert(addr, value);
+    }
+
+    fn manual(&self) -> bool {
+        self.reg(ADDR_FAN_MODE) & FAN_MODE_MANUAL != 0
+    }
+
+    // 当前风扇占空比(%)，自动模式下按温度估算
+    pub fn duty(&self) -> (f64, f64) {
+        if self.manual() {
+            return (self.reg(ADDR_LEFT_DUTY) as f64 / 2.0, self.reg(ADDR_RIGHT_DUTY) as f64 / 2.0);
+        }
+        let auto = |t: f64| ((t - 40.0) * 2.0).clamp(20.0, 100.0);
+        (auto(self.cpu_temp), auto(self.gpu_temp))
+    }
+
+    fn load(&self, now: Instant) -> (f64, f64) {
+        self.load.unwrap_or_else(|| {
+            let t = now.duration_since(self.started).as_secs_f64();
+            (0.5 + 0.4 * (t / 60.0).sin(), 0.4 + 0.3 * (t / 90.0).cos())
+        })
+    }
+
+    // 按经过的时间推进温度
+    pub fn advance(&mut self, now: Instant) {
+        let dt = now.duration_since(self.last).as_secs_f64();
+        self.last = now;
+        let (cpu_load, gpu_load) = self.load(now);
+        let (left, right) = self.duty();
+        let cpu_target = AMBIENT + cpu_load * 75.0 - left * 0.3;
+        let gpu_target = AMBIENT + gpu_load * 65.0 - right * 0.25;
+        let k = 1.0 - (-dt / TIME_CONSTANT).exp();
+        self.cpu_temp += (cpu_target.max(AMBIENT) - self.cpu_temp) * k;
+        self.gpu_temp += (gpu_target.max(AMBIENT) - self.gpu_temp) * k;
+        self.update_sensors();
+    }
+
+    fn update_sensors(&mut self) {
+        let (left, right) = self.duty();
+        let cpu = self.cpu_temp.round().clamp(0.0, 255.0) as u8;
+        let gpu = self.gpu_temp.round().clamp(0.0, 255.0) as u8;
+        self.set_reg(ADDR_CPU_TEMP, cpu);
+        self.set_reg(ADDR_GPU_TEMP, gpu);
+        for (addr, duty) in [(ADDR_LEFT_RPM, left), (ADDR_RIGHT_RPM, right)] {
+            let rpm = (duty * RPM_PER_DUTY) as u16;
+            self.set_reg(addr, (rpm >> 8) as u8);
+            self.set_reg(addr + 1, (rpm & 0xFF) as u8);
+        }
+    }
+
+    pub fn read(&mut self, addr: u16) -> i64 {
+        self.advance(Instant::now());
+        let value = self.reg(addr) as i64;
+        if addr == ADDR_FAN_MODE {
+            return FAN_MODE_HIGH | value;
+        }
+        value
+    }
+
+    pub fn write(&mut self, addr: u16, value: u8) -> i64 {
+        self.advance(Instant::now());
+        match (addr, value) {
+            (ADDR_FAN_MODE, _) if self.fan_mode_stuck.is_some() => {}
+            (ADDR_FAN_MODE, FAN_MODE_RESET) => self.set_reg(ADDR_FAN_MODE, FAN_MODE_AUTO),
+            (ADDR_FAN_MODE, FAN_MODE_MANUAL) => {
+                let mode = self.reg(ADDR_FAN_MODE) | FAN_MODE_MANUAL;
+                self.set_reg(ADDR_FAN_MODE, mode);
+            }
+            _ => self.set_reg(addr, value),
+        }
+        self.update_sensors();
+        0
+    }
+
+    // 解析 GetSetULong 参数，bit40 为读标志
+    pub fn call(&mut self, data: &str) -> i64 {
+        let data = u64::from_str_radix(data.trim_start_matches("0x"), 16).unwrap_or(0);
+        let addr = (data & 0xFFFF) as u16;
+        if data & (1 << 40) != 0 {
+            self.read(addr)
+        } else {
+            self.write(addr, ((data >> 16) & 0xFF) as u8)
+        }
+    }
+}
+
+impl Default for SimState {
+    fn default() -> Self {
+        SimState::new()
+    }
+}
+
+static SIM_STATE: OnceLock<Arc<Mutex<SimState>>> = OnceLock::new();
+
+pub struct SimEc(Arc<Mutex<SimState>>);
+
+impl SimEc {
+    // 进程内所有模拟 EC 句柄共享同一份寄存器
+    pub fn shared() -> Self {
+        SimEc(Arc::clone(SIM_STATE.get_or_init(|| Arc::new(Mutex::new(SimState::new())))))
+    }
+
+    pub fn new(state: SimState) -> Self {
+        SimEc(Arc::new(Mutex::new(state)))
+    }
+
+    pub fn state(&self) -> &Arc<Mutex<SimState>> {
+        &self.0
+    }
+}
+
+impl Ec for SimEc {
+    fn call(&self, data: &str) -> i64 {
+        self.0.lock().unwrap().call(data)
+    }
+}
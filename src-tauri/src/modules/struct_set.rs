@@ -1,6 +1,8 @@
+use std::collections::BTreeMap;
 use std::sync::{Arc, Mutex};
-use std::sync::mpsc::Sender;
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use crate::modules::{power::{PowerSource, PowerStatus}, recorder::TelemetryRecorder, telemetry::TelemetryBuffer};
 
 #[derive(Serialize, Deserialize)]
 pub struct FanPoint {
@@ -22,7 +24,7 @@ pub struct FanSpeeds {
     pub right_temp: i64,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Tdp {
     pub cpu1: i64,
     pub cpu2: i64,
@@ -31,15 +33,515 @@ pub struct Tdp {
     pub tcc: i64,
 }
 
-pub struct FanControlState {
-    pub is_running: Arc<Mutex<bool>>,
+// 完整的功耗墙设置(W)，gpu1/gpu2/tcc 与 Tdp 对应同一组寄存器
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct PowerLimits {
+    pub pl1: i64,
+    pub pl2: i64,
+    pub pl4: i64,
+    // 整机 PSYS PL1，两个字节
+    pub psys_pl1: i64,
+    pub gpu1: i64,
+    pub gpu2: i64,
+    // 温度墙偏移(℃)
+    pub tcc: i64,
+    // 旧的功耗配置没有这一项，为空时不修改
+    #[serde(default)]
+    pub nv_oc: Option<NvOc>,
+}
+
+// NVIDIA 显卡超频/TGP 设置(W)
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct NvOc {
+    pub enabled: bool,
+    // Configurable TGP
+    pub ctgp: u8,
+    // 显卡动态加速时 CPU 的功耗目标
+    pub cpu_power_target: u8,
+    pub max_tgp: u8,
+}
+
+// EC 内置的各模式出厂默认值，mode 2 即 BATTERYSAVER
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ModeDefaults {
+    pub mode: u8,
+    pub pl1: i64,
+    pub pl2: i64,
+    pub pl4: i64,
+    pub dstate: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PollConfig {
+    // 固定采样间隔(ms)
+    pub interval_ms: u64,
+    // 自适应采样开关
+    pub adaptive: bool,
+    // 升温过快或接近阈值时的采样间隔(ms)
+    pub fast_interval_ms: u64,
+    // 稳定低温时的采样间隔(ms)
+    pub slow_interval_ms: u64,
+    // 升温速率阈值(℃/s)
+    pub rise_rate: f64,
+    // 距离过热阈值多少℃视为接近阈值
+    pub near_margin: i64,
+    // 低于该温度才允许降低采样频率
+    pub idle_temp: i64,
+    // 连续稳定多少次后降低采样频率
+    pub idle_ticks: u32,
+}
+
+impl Default for PollConfig {
+    fn default() -> Self {
+        PollConfig {
+            interval_ms: 1000,
+            adaptive: false,
+            fast_interval_ms: 250,
+            slow_interval_ms: 3000,
+            rise_rate: 2.0,
+            near_margin: 5,
+            idle_temp: 60,
+            idle_ticks: 5,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DaemonConfig {
+    // 守护进程启动时自动运行的风扇配置，为空时保持 EC 自动控制
+    pub profile: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub enabled: bool,
+    // 默认只监听本机
+    pub bind: String,
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        MetricsConfig {
+            enabled: false,
+            bind: "127.0.0.1:9184".to_string(),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct MqttConfig {
+    pub enabled: bool,
+    pub host: String,
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    pub client_id: String,
+    // 状态和命令主题前缀
+    pub base_topic: String,
+    // Home Assistant 自动发现前缀
+    pub discovery_prefix: String,
+    // 状态最短发布间隔，避免快速采样时刷屏
+    pub publish_interval_ms: u64,
+}
+
+impl Default for MqttConfig {
+    fn default() -> Self {
+        MqttConfig {
+            enabled: false,
+            host: "127.0.0.1".to_string(),
+            port: 1883,
+            username: None,
+            password: None,
+            client_id: "nuc_x15_fan".to_string(),
+            base_topic: "nuc_x15_fan".to_string(),
+            discovery_prefix: "homeassistant".to_string(),
+            publish_interval_ms: 5000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct RestConfig {
+    pub enabled: bool,
+    pub bind: String,
+    pub port: u16,
+    // 为空时首次启动自动生成并保存
+    pub token: String,
+}
+
+impl Default for RestConfig {
+    fn default() -> Self {
+        RestConfig {
+            enabled: false,
+            bind: "127.0.0.1".to_string(),
+            port: 9185,
+            token: String::new(),
+        }
+    }
+}
+
+// 电池相关的持久化设置，守护进程启动时重新写入 EC
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BatteryConfig {
+    // 充电上限(%)，为空时不修改 EC 设置
+    pub charge_limit: Option<u8>,
+    // 双速率充电设置，为空时不修改 EC 设置
+    pub charge_schedule: Option<ChargeSchedule>,
+}
+
+// 双速率充电: 第二速率时间段内按 second_rate 充电，其余时间按 first_rate
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct ChargeSchedule {
+    // 充电速率(%)
+    pub first_rate: u8,
+    pub second_rate: u8,
+    // "HH:MM"
+    pub second_start: String,
+    pub second_end: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+// 键盘灯预设，接电源和用电池时分别设置颜色
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct RgbPreset {
+    pub ac: Rgb,
+    pub battery: Rgb,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RgbConfig {
+    pub presets: BTreeMap<String, RgbPreset>,
+    // 守护进程启动时重新应用的预设
+    pub active: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct LightbarState {
+    pub on: bool,
+    pub color: Rgb,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ThermalSource {
+    // CPU/GPU 中较高的温度
+    #[default]
+    Temp,
+    // 左右风扇中较高的占空比，EC 自动控制时为 0
+    Duty,
+}
+
+// 按温度或风扇档位在 cold 和 hot 之间渐变
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ThermalIndicator {
+    pub enabled: bool,
+    pub source: ThermalSource,
+    // 低于 low 显示 cold，高于 high 显示 hot，单位为℃或%
+    pub low: i64,
+    pub high: i64,
+    pub cold: Rgb,
+    pub hot: Rgb,
+    // 两次写入 EC 的最小间隔(ms)
+    pub interval_ms: u64,
+}
+
+impl Default for ThermalIndicator {
+    fn default() -> Self {
+        ThermalIndicator {
+            enabled: false,
+            source: ThermalSource::Temp,
+            low: 45,
+            high: 90,
+            cold: Rgb { r: 0, g: 0, b: 255 },
+            hot: Rgb { r: 255, g: 0, b: 0 },
+            interval_ms: 2000,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct LightbarConfig {
+    // 手动设置的灯条状态，为空时不修改 EC 设置
+    pub manual: Option<LightbarState>,
+    // 启用时由控制循环接管灯条
+    pub thermal: ThermalIndicator,
+}
+
+#[derive(Clone)]
+pub struct LightbarConfigState {
+    pub config: Arc<Mutex<LightbarConfig>>,
+}
+
+// EC 固件自带的风扇控制，应用退出后仍然有效
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct VendorFanSettings {
+    // ADDR_MyFanCCI_Mode_Index，1~3 对应 Profile1~3，0 为固件默认
+    pub mode: u8,
+    // MyFan3 L1~L5 五档 PWM 原始值，应逐档不减
+    pub curve: [u8; 5],
+    // MYFANI 最低转速、最低温度和额外转速
+    pub min_speed: u8,
+    pub min_temp: u8,
+    pub extra_speed: u8,
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct VendorFanConfig {
+    // 为空时不启用固件风扇控制
+    pub settings: Option<VendorFanSettings>,
+}
+
+// 机型能力库中的一项，内置条目见 devices.json，device_config.json 中的条目优先匹配
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct DeviceCapability {
+    pub name: String,
+    // ADDR_AP_PROJECT_ID，按此项识别机型，为空的条目不参与匹配
+    pub project_ids: Vec<u8>,
+    // OEM/BIOS 字节，键名见 device.rs 中的 OEM_BYTES，给出的项都要相同
+    pub oem: BTreeMap<String, u8>,
+    // 0x751 读出值的高字节，只作附加校验，为空时不校验
+    pub fan_mode_high: Option<u8>,
+    // 风扇数量，为 1 时只读写左风扇
+    pub fans: u8,
+    // 可读的传感器，对应快照字段和 battery，为空时全部读取
+    pub sensors: Vec<String>,
+    // 支持的功能，对应 RPC 方法前缀，如 rgb、lightbar、vendor_fan
+    pub features: Vec<String>,
+    // PL1/PL2/PL4 和显卡 TGP 上限(W)
+    pub max_pl: i64,
+    pub max_tgp: u8,
+    // 1926 的实际用途，为空时按 TCC 偏移处理，不写固件风扇 L1
+    pub reg_1926: Option<Reg1926>,
+    // 固件风扇五档表的温度点(℃)，为空时按 min_temp 到过热温度等分估算
+    pub fan_breakpoints: Option<[u8; 5]>,
+}
+
+// 分析.md 中 1926 既是 ADDR_TCC_OFFSET 又是 ADDR_L1_PWM_DEFAULT_MYFAN3
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Reg1926 {
+    TccOffset,
+    Myfan3L1,
+}
+
+impl Default for DeviceCapability {
+    fn default() -> Self {
+        DeviceCapability {
+            name: String::new(),
+            project_ids: Vec::new(),
+            oem: BTreeMap::new(),
+            fan_mode_high: None,
+            fans: 2,
+            sensors: Vec::new(),
+            features: Vec::new(),
+            max_pl: 255,
+            max_tgp: 175,
+            reg_1926: None,
+            fan_breakpoints: None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct DeviceConfig {
+    // 允许在未识别的机型上写 EC
+    pub allow_unknown: bool,
+    pub devices: Vec<DeviceCapability>,
+}
+
+#[derive(Clone)]
+pub struct PollConfigState {
+    pub config: Arc<Mutex<PollConfig>>,
+}
+
+// 按供电方式自动切换风扇配置和 TDP
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct PowerConfig {
+    pub enabled: bool,
+    // 供电方式稳定多久后才切换(ms)
+    pub debounce_ms: u64,
+    // 按顺序匹配，取第一条
+    pub rules: Vec<PowerRule>,
+}
+
+impl Default for PowerConfig {
+    fn default() -> Self {
+        PowerConfig {
+            enabled: false,
+            debounce_ms: 5000,
+            rules: Vec::new(),
+        }
+    }
 }
 
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PowerRule {
+    pub source: PowerSource,
+    // Type-C 适配器功率不低于该值(W)时才匹配
+    #[serde(default)]
+    pub min_watt: Option<u8>,
+    // 风扇配置名，auto 为 EC 自动控制
+    pub profile: String,
+    // 为空时不修改 TDP
+    #[serde(default)]
+    pub tdp: Option<Tdp>,
+    // 功耗配置名，在 tdp 之后应用
+    #[serde(default)]
+    pub power_profile: Option<String>,
+}
+
+#[derive(Clone)]
+pub struct PowerConfigState {
+    pub config: Arc<Mutex<PowerConfig>>,
+}
 
-pub struct ChannelControlState {
-    pub tx: Arc<Mutex<Sender<String>>>
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    // 内存中保留的历史时长(s)
+    pub retention_secs: u64,
+    // 是否写入磁盘日志
+    pub record_to_disk: bool,
+    // 单个日志文件大小上限(KB)
+    pub max_file_kb: u64,
+    // 日志目录总大小上限(MB)
+    pub max_total_mb: u64,
+    // 日志保留时长(h)
+    pub max_age_hours: u64,
 }
 
-pub struct ChannelControlState64 {
-    pub tx: Arc<Mutex<Sender<i64>>>
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        TelemetryConfig {
+            retention_secs: 3600,
+            record_to_disk: false,
+            max_file_kb: 4096,
+            max_total_mb: 64,
+            max_age_hours: 72,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TelemetrySample {
+    // 采样时间(ms, Unix 时间戳)
+    pub ts: u64,
+    pub cpu_temp: i64,
+    pub gpu_temp: i64,
+    pub left_fan_speed: i64,
+    pub right_fan_speed: i64,
+    // 下发的风扇占空比(%)
+    pub left_duty: i64,
+    pub right_duty: i64,
+    pub profile: String,
+    // EC 上报的适配器功率(W)
+    pub apc_watt: i64,
+    pub cpu_pl1: i64,
+    pub cpu_pl2: i64,
+    // 电池剩余电量(%)和电流(mA，放电为负)，控制循环定期刷新
+    #[serde(default)]
+    pub battery_percent: i64,
+    #[serde(default)]
+    pub battery_current: i64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TelemetryEvent {
+    pub ts: u64,
+    // start / stop / stage / overheat / overheat_clear
+    pub kind: String,
+    pub detail: String,
+}
+
+// 磁盘日志中的一行
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "t")]
+pub enum TelemetryRecord {
+    #[serde(rename = "s")]
+    Sample(TelemetrySample),
+    #[serde(rename = "e")]
+    Event(TelemetryEvent),
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct TelemetryStat {
+    pub min: i64,
+    pub max: i64,
+    pub avg: f64,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TelemetryPoint {
+    // 区间起始时间(ms)
+    pub ts: u64,
+    pub count: usize,
+    pub cpu_temp: TelemetryStat,
+    pub gpu_temp: TelemetryStat,
+    pub left_fan_speed: TelemetryStat,
+    pub right_fan_speed: TelemetryStat,
+    pub left_duty: TelemetryStat,
+    pub right_duty: TelemetryStat,
+    pub apc_watt: TelemetryStat,
+    pub cpu_pl1: TelemetryStat,
+    pub cpu_pl2: TelemetryStat,
+    pub battery_percent: TelemetryStat,
+    pub battery_current: TelemetryStat,
+    // 区间内最后一次采样的配置名
+    pub profile: String,
+}
+
+#[derive(Clone)]
+pub struct TelemetryState {
+    pub buffer: Arc<Mutex<TelemetryBuffer>>,
+    pub recorder: Arc<Mutex<TelemetryRecorder>>,
+    // 事件订阅，没有订阅者时发送失败直接忽略
+    pub events: broadcast::Sender<TelemetryEvent>,
+}
+
+// 控制循环每次采样推送给前端的快照
+#[derive(Serialize, Deserialize, Clone)]
+pub struct SensorSnapshot {
+    #[serde(flatten)]
+    pub sample: TelemetrySample,
+    pub running: bool,
+    // overheat / sensor_range / left_fan_stall / right_fan_stall / fan_mode_failed
+    pub faults: Vec<String>,
+    // 本次采样和调速耗时
+    #[serde(default)]
+    pub tick_ms: f64,
+    #[serde(default)]
+    pub power: PowerStatus,
+}
+
+#[derive(Clone)]
+pub struct SensorState {
+    pub latest: Arc<Mutex<Option<SensorSnapshot>>>,
+    pub updates: broadcast::Sender<SensorSnapshot>,
+}
+
+#[derive(Clone)]
+pub struct FanControlState {
+    pub is_running: Arc<Mutex<bool>>,
+    pub fan_data: Arc<Mutex<Option<serde_json::Value>>>,
+    // 当前运行的配置名
+    pub profile: Arc<Mutex<String>>,
 }
\ No newline at end of file
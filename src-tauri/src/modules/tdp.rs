@@ -0,0 +1,345 @@
+use std::fs;
+use std::path::PathBuf;
+use crate::modules::struct_set::{ModeDefaults, NvOc, PowerLimits, Tdp};
+use crate::modules::device::{current_capability, reg_1926_is_l1};
+use crate::modules::ec::{ec_read, ec_read_u16, ec_write_all, Ec, WriteReport};
+use crate::modules::profile::{check_name, get_profile_dir};
+
+const ADDR_PSYS_PL1_VALUE_BYTE1: u16 = 1824;
+const ADDR_PSYS_PL1_VALUE_BYTE2: u16 = 1825;
+const ADDR_GPU1: u16 = 0x73d;
+const ADDR_GPU2: u16 = 0x733;
+const ADDR_NV_OC_CONTROL_BYTE: u16 = 1859;
+const ADDR_NV_OC_CTGP_BYTE: u16 = 1860;
+const ADDR_NV_OC_CPU_POWER_TARGET_BYTE: u16 = 1861;
+const ADDR_NV_OC_MAXTGP_BYTE: u16 = 1862;
+const ADDR_PL1_SETTING_VALUE: u16 = 1923;
+const ADDR_PL2_SETTING_VALUE: u16 = 1924;
+const ADDR_PL4_SETTING_VALUE: u16 = 1925;
+const ADDR_TCC_OFFSET: u16 = 1926;
+
+// 显卡 TGP 上限(W)，超出的值 EC 不会拒绝但显卡驱动会异常
+pub const NV_OC_MAX_WATT: u8 = 175;
+
+// 每个模式依次为 PL1、PL2、PL4、DState
+const ADDR_MODE_DEFAULTS: [u16; 3] = [1840, 1844, 1959];
+
+// WMI 读出值带高字节，全部按单字节取，读出的值可原样写回
+pub fn read_tdp(ec: &dyn Ec) -> Tdp {
+    Tdp {
+        cpu1: ec_read(ec, ADDR_PL1_SETTING_VALUE),
+        cpu2: ec_read(ec, ADDR_PL2_SETTING_VALUE),
+        gpu1: ec_read(ec, ADDR_GPU1),
+        gpu2: ec_read(ec, ADDR_GPU2),
+        tcc: ec_read(ec, ADDR_TCC_OFFSET),
+    }
+}
+
+// 寄存器为单字节，PL1/PL2 另受机型能力库上限约束，与 check_power_limits 一致
+pub fn check_tdp(t: &Tdp) -> Result<(), String> {
+    for (name, value) in [("cpu1", t.cpu1), ("cpu2", t.cpu2), ("gpu1", t.gpu1), ("gpu2", t.gpu2), ("tcc", t.tcc)] {
+        if !(0..=255).contains(&value) {
+            return Err(format!("{} 超出范围 0~255: {}", name, value));
+        }
+    }
+    if let Some(c) = current_capability() {
+        for (name, value) in [("cpu1", t.cpu1), ("cpu2", t.cpu2)] {
+            if value > c.max_pl {
+                return Err(format!("{} 超出 {} 的上限 {}W: {}", name, c.name, c.max_pl, value));
+            }
+        }
+    }
+    Ok(())
+}
+
+// 1926 在机型条目中为固件风扇 L1 时，写 TCC 偏移就是改写 L1，只允许写回当前值
+fn check_tcc_with(ec: &dyn Ec, tcc: i64, l1: bool) -> Result<(), String> {
+    let current = ec_read(ec, ADDR_TCC_OFFSET);
+    if l1 && tcc != current {
+        return Err(format!("1926 在当前机型上是固件风扇 L1，不能修改 TCC 偏移: 当前值 {}，写入 {}", current, tcc));
+    }
+    Ok(())
+}
+
+// 参数无效时返回 Err，不写任何寄存器
+pub fn write_tdp(ec: &dyn Ec, t: &Tdp) -> Result<WriteReport, String> {
+    check_tdp(t)?;
+    check_tcc_with(ec, t.tcc, reg_1926_is_l1())?;
+    Ok(ec_write_all(ec, &[
+        (ADDR_GPU1, t.gpu1 as u8),
+        (ADDR_GPU2, t.gpu2 as u8),
+        (ADDR_PL1_SETTING_VALUE, t.cpu1 as u8),
+        (ADDR_PL2_SETTING_VALUE, t.cpu2 as u8),
+        (ADDR_TCC_OFFSET, t.tcc as u8),
+    ]))
+}
+
+pub fn read_power_limits(ec: &dyn Ec) -> PowerLimits {
+    PowerLimits {
+        pl1: ec_read(ec, ADDR_PL1_SETTING_VALUE),
+        pl2: ec_read(ec, ADDR_PL2_SETTING_VALUE),
+        pl4: ec_read(ec, ADDR_PL4_SETTING_VALUE),
+        psys_pl1: ec_read_u16(ec, ADDR_PSYS_PL1_VALUE_BYTE1) as i64,
+        gpu1: ec_read(ec, ADDR_GPU1),
+        gpu2: ec_read(ec, ADDR_GPU2),
+        tcc: ec_read(ec, ADDR_TCC_OFFSET),
+        nv_oc: Some(read_nv_oc(ec)),
+    }
+}
+
+pub fn read_nv_oc(ec: &dyn Ec) -> NvOc {
+    NvOc {
+        enabled: ec_read(ec, ADDR_NV_OC_CONTROL_BYTE) != 0,
+        ctgp: ec_read(ec, ADDR_NV_OC_CTGP_BYTE) as u8,
+        cpu_power_target: ec_read(ec, ADDR_NV_OC_CPU_POWER_TARGET_BYTE) as u8,
+        max_tgp: ec_read(ec, ADDR_NV_OC_MAXTGP_BYTE) as u8,
+    }
+}
+
+pub fn check_nv_oc(oc: &NvOc) -> Result<(), String> {
+    let max = current_capability().map(|c| c.max_tgp.min(NV_OC_MAX_WATT)).unwrap_or(NV_OC_MAX_WATT);
+    for (name, value) in [("ctgp", oc.ctgp), ("cpu_power_target", oc.cpu_power_target), ("max_tgp", oc.max_tgp)] {
+        if value > max {
+            return Err(format!("{} 超出范围 0~{}: {}", name, max, value));
+        }
+    }
+    if oc.ctgp > oc.max_tgp {
+        return Err(format!("ctgp 不能大于 max_tgp: {} {}", oc.ctgp, oc.max_tgp));
+    }
+    Ok(())
+}
+
+pub fn write_nv_oc(ec: &dyn Ec, oc: &NvOc) -> Result<WriteReport, String> {
+    check_nv_oc(oc)?;
+    Ok(ec_write_all(ec, &nv_oc_writes(oc)))
+}
+
+// 先写数值再写控制字节，开启时 EC 直接使用新值
+fn nv_oc_writes(oc: &NvOc) -> [(u16, u8); 4] {
+    [
+        (ADDR_NV_OC_CTGP_BYTE, oc.ctgp),
+        (ADDR_NV_OC_CPU_POWER_TARGET_BYTE, oc.cpu_power_target),
+        (ADDR_NV_OC_MAXTGP_BYTE, oc.max_tgp),
+        (ADDR_NV_OC_CONTROL_BYTE, oc.enabled as u8),
+    ]
+}
+
+// 0 表示不限制，不参与大小比较
+pub fn check_power_limits(l: &PowerLimits) -> Result<(), String> {
+    for (name, value) in [("pl1", l.pl1), ("pl2", l.pl2), ("pl4", l.pl4), ("gpu1", l.gpu1), ("gpu2", l.gpu2), ("tcc", l.tcc)] {
+        if !(0..=255).contains(&value) {
+            return Err(format!("{} 超出范围 0~255: {}", name, value));
+        }
+    }
+    // 机型能力库中的安全上限
+    if let Some(c) = current_capability() {
+        for (name, value) in [("pl1", l.pl1), ("pl2", l.pl2), ("pl4", l.pl4)] {
+            if value > c.max_pl {
+                return Err(format!("{} 超出 {} 的上限 {}W: {}", name, c.name, c.max_pl, value));
+            }
+        }
+    }
+    if !(0..=0xFFFF).contains(&l.psys_pl1) {
+        return Err(format!("psys_pl1 超出范围 0~65535: {}", l.psys_pl1));
+    }
+    let ordered: Vec<i64> = [l.pl1, l.pl2, l.pl4].into_iter().filter(|v| *v > 0).collect();
+    if ordered.windows(2).any(|w| w[0] > w[1]) {
+        return Err(format!("应满足 PL1 <= PL2 <= PL4: {} {} {}", l.pl1, l.pl2, l.pl4));
+    }
+    if let Some(oc) = &l.nv_oc {
+        check_nv_oc(oc)?;
+    }
+    Ok(())
+}
+
+// 参数无效时返回 Err，写入校验结果在 WriteReport 中
+pub fn write_power_limits(ec: &dyn Ec, l: &PowerLimits) -> Result<WriteReport, String> {
+    check_power_limits(l)?;
+    check_tcc_with(ec, l.tcc, reg_1926_is_l1())?;
+    let mut writes = vec![
+        (ADDR_PL1_SETTING_VALUE, l.pl1 as u8),
+        (ADDR_PL2_SETTING_VALUE, l.pl2 as u8),
+        (ADDR_PL4_SETTING_VALUE, l.pl4 as u8),
+        (ADDR_PSYS_PL1_VALUE_BYTE1, (l.psys_pl1 >> 8) as u8),
+        (ADDR_PSYS_PL1_VALUE_BYTE2, (l.psys_pl1 & 0xFF) as u8),
+        (ADDR_GPU1, l.gpu1 as u8),
+        (ADDR_GPU2, l.gpu2 as u8),
+        (ADDR_TCC_OFFSET, l.tcc as u8),
+    ];
+    if let Some(oc) = &l.nv_oc {
+        writes.extend(nv_oc_writes(oc));
+    }
+    Ok(ec_write_all(ec, &writes))
+}
+
+pub fn read_mode_defaults(ec: &dyn Ec) -> Vec<ModeDefaults> {
+    ADDR_MODE_DEFAULTS
+        .iter()
+        .enumerate()
+        .map(|(mode, &addr)| ModeDefaults {
+            mode: mode as u8,
+            pl1: ec_read(ec, addr),
+            pl2: ec_read(ec, addr + 1),
+            pl4: ec_read(ec, addr + 2),
+            dstate: ec_read(ec, addr + 3),
+        })
+        .collect()
+}
+
+// 把某个模式的出厂 PL1/PL2/PL4 写回设置寄存器，其余项保持不变
+pub fn restore_mode_defaults(ec: &dyn Ec, mode: u8) -> Result<PowerLimits, String> {
+    let defaults = read_mode_defaults(ec)
+        .into_iter()
+        .find(|d| d.mode == mode)
+        .ok_or_else(|| format!("无效的模式: {}", mode))?;
+    let mut limits = read_power_limits(ec);
+    limits.pl1 = defaults.pl1;
+    limits.pl2 = defaults.pl2;
+    limits.pl4 = defaults.pl4;
+    write_power_limits(ec, &limits)?.check()?;
+    Ok(limits)
+}
+
+// 功耗配置和风扇配置放在同一目录下的 power 子目录
+pub fn get_power_profile_dir() -> PathBuf {
+    get_profile_dir().join("power")
+}
+
+fn power_profile_path(name: &str) -> Result<PathBuf, String> {
+    check_name(name)?;
+    let dir = get_power_profile_dir();
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join(format!("{}.json", name)))
+}
+
+pub fn list_power_profiles() -> Vec<String> {
+    let mut names: Vec<String> = fs::read_dir(get_power_profile_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().to_string_lossy().strip_suffix(".json").map(|n| n.to_string()))
+                .collect()
+        })
+        .unwrap_or_default();
+    names.sort();
+    names
+}
+
+pub fn load_power_profile(name: &str) -> Result<PowerLimits, String> {
+    let path = power_profile_path(name)?;
+    let json_data = fs::read_to_string(&path).map_err(|_| format!("功耗配置不存在: {}", name))?;
+    serde_json::from_str(&json_data).map_err(|e| e.to_string())
+}
+
+pub fn save_power_profile(name: &str, limits: &PowerLimits) -> Result<(), String> {
+    check_power_limits(limits)?;
+    let json_data = serde_json::to_string_pretty(limits).map_err(|e| e.to_string())?;
+    fs::write(power_profile_path(name)?, json_data).map_err(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sim::{SimEc, SimState};
+
+    fn limits(ec: &dyn Ec) -> PowerLimits {
+        PowerLimits { pl1: 45, pl2: 90, pl4: 120, ..read_power_limits(ec) }
+    }
+
+    // 真实 WMI 读出值带高字节，模拟 EC 只返回单字节
+    struct HighByteEc(SimEc);
+
+    impl Ec for HighByteEc {
+        fn call(&self, data: &str) -> i64 {
+            let value = self.0.call(data);
+            let read = u64::from_str_radix(data.trim_start_matches("0x"), 16).map(|cmd| cmd >> 40 == 1).unwrap_or(false);
+            if read && value >= 0 { value | 0x6C00 } else { value }
+        }
+    }
+
+    const OC: NvOc = NvOc { enabled: true, ctgp: 120, cpu_power_target: 55, max_tgp: 150 };
+
+    #[test]
+    fn nv_oc_out_of_range_is_rejected_without_writing() {
+        let ec = SimEc::new(SimState::new());
+        let before = read_nv_oc(&ec);
+        for oc in [
+            NvOc { ctgp: 160, ..OC },
+            NvOc { cpu_power_target: NV_OC_MAX_WATT + 1, ..OC },
+            NvOc { ctgp: NV_OC_MAX_WATT + 1, max_tgp: NV_OC_MAX_WATT + 1, ..OC },
+        ] {
+            assert!(check_nv_oc(&oc).is_err(), "{:?}", oc);
+            assert!(write_nv_oc(&ec, &oc).is_err(), "{:?}", oc);
+            assert!(write_power_limits(&ec, &PowerLimits { nv_oc: Some(oc), ..limits(&ec) }).is_err(), "{:?}", oc);
+        }
+        assert_eq!(read_nv_oc(&ec), before);
+    }
+
+    #[test]
+    fn nv_oc_write_reads_back() {
+        let ec = SimEc::new(SimState::new());
+        let report = write_nv_oc(&ec, &OC).unwrap();
+        assert_eq!(report.writes.len(), 4);
+        report.check().unwrap();
+        assert_eq!(read_nv_oc(&ec), OC);
+        // 关闭时保留数值，只清控制字节
+        write_nv_oc(&ec, &NvOc { enabled: false, ..OC }).unwrap().check().unwrap();
+        assert_eq!(read_nv_oc(&ec), NvOc { enabled: false, ..OC });
+    }
+
+    #[test]
+    fn tcc_is_locked_when_1926_is_l1() {
+        let ec = SimEc::new(SimState::new());
+        let current = ec_read(&ec, ADDR_TCC_OFFSET);
+        check_tcc_with(&ec, current, true).unwrap();
+        assert!(check_tcc_with(&ec, current + 1, true).is_err());
+        check_tcc_with(&ec, current + 1, false).unwrap();
+    }
+
+    #[test]
+    fn tdp_out_of_range_is_rejected_without_writing() {
+        let ec = SimEc::new(SimState::new());
+        let before = read_tdp(&ec);
+        for t in [
+            Tdp { cpu1: 300, ..before.clone() },
+            Tdp { cpu2: -1, ..before.clone() },
+            Tdp { tcc: 256, ..before.clone() },
+        ] {
+            assert!(write_tdp(&ec, &t).is_err(), "{:?}", t);
+        }
+        assert_eq!(read_tdp(&ec), before);
+        let t = Tdp { cpu1: 45, cpu2: 90, ..before };
+        write_tdp(&ec, &t).unwrap().check().unwrap();
+        assert_eq!((read_tdp(&ec).cpu1, read_tdp(&ec).cpu2), (45, 90));
+    }
+
+    #[test]
+    fn power_limits_out_of_range_are_rejected_without_writing() {
+        let ec = SimEc::new(SimState::new());
+        let before = read_power_limits(&ec);
+        for l in [
+            PowerLimits { pl4: 300, ..limits(&ec) },
+            PowerLimits { gpu1: 256, ..limits(&ec) },
+            PowerLimits { psys_pl1: 0x10000, ..limits(&ec) },
+            PowerLimits { pl1: 100, pl2: 90, ..limits(&ec) },
+        ] {
+            assert!(write_power_limits(&ec, &l).is_err(), "{:?}", l);
+        }
+        assert_eq!(read_power_limits(&ec), before);
+        write_power_limits(&ec, &limits(&ec)).unwrap().check().unwrap();
+        assert_eq!(read_power_limits(&ec), limits(&ec));
+    }
+
+    #[test]
+    fn tdp_read_with_high_byte_writes_back() {
+        let ec = HighByteEc(SimEc::new(SimState::new()));
+        let tdp = read_tdp(&ec);
+        for value in [tdp.cpu1, tdp.cpu2, tdp.gpu1, tdp.gpu2, tdp.tcc] {
+            assert!((0..=255).contains(&value), "{:?}", tdp);
+        }
+        assert_eq!((tdp.cpu1, tdp.cpu2), (45, 115));
+        check_tdp(&tdp).unwrap();
+        assert!(write_tdp(&ec, &tdp).unwrap().ok);
+        assert_eq!(read_tdp(&ec), tdp);
+    }
+}
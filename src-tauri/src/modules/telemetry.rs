@@ -0,0 +1,386 @@
+use std::collections::VecDeque;
+use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+use crate::modules::recorder::{read_records, TelemetryRecorder};
+use crate::modules::struct_set::{
+    TelemetryConfig, TelemetryEvent, TelemetryPoint, TelemetryRecord, TelemetrySample, TelemetryStat, TelemetryState,
+};
+
+// 采样间隔最短 100ms，按此估算容量上限，防止配置过大占满内存
+const MIN_SAMPLE_MS: u64 = 100;
+const MAX_SAMPLES: usize = 864000;
+
+// 配置上限，防止换算成字节或毫秒时溢出
+const MAX_RETENTION_SECS: u64 = 7 * 24 * 3600;
+const MAX_FILE_KB: u64 = 1024 * 1024;
+const MAX_TOTAL_MB: u64 = 100 * 1024;
+const MAX_AGE_HOURS: u64 = 365 * 24;
+
+pub fn check_telemetry_config(config: &TelemetryConfig) -> Result<(), String> {
+    for (name, value, max) in [
+        ("retention_secs", config.retention_secs, MAX_RETENTION_SECS),
+        ("max_file_kb", config.max_file_kb, MAX_FILE_KB),
+        ("max_total_mb", config.max_total_mb, MAX_TOTAL_MB),
+        ("max_age_hours", config.max_age_hours, MAX_AGE_HOURS),
+    ] {
+        if !(1..=max).contains(&value) {
+            return Err(format!("{} 超出范围 1~{}: {}", name, max, value));
+        }
+    }
+    // 总大小放不下一个文件时每次滚动都会删光历史
+    if config.max_total_mb * 1024 < config.max_file_kb {
+        return Err("max_total_mb 不能小于 max_file_kb".to_string());
+    }
+    Ok(())
+}
+
+pub fn now_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis() as u64).unwrap_or(0)
+}
+
+pub struct TelemetryBuffer {
+    samples: VecDeque<TelemetrySample>,
+    events: VecDeque<TelemetryEvent>,
+    retention_ms: u64,
+}
+
+impl TelemetryBuffer {
+    pub fn new(retention_secs: u64) -> Self {
+        TelemetryBuffer {
+            samples: VecDeque::new(),
+            events: VecDeque::new(),
+            retention_ms: retention_secs.saturating_mul(1000),
+        }
+    }
+
+    pub fn set_retention(&mut self, retention_secs: u64) {
+        self.retention_ms = retention_secs.saturating_mul(1000);
+        self.evict();
+    }
+
+    pub fn push(&mut self, sample: TelemetrySample) {
+        self.samples.push_back(sample);
+        self.evict();
+    }
+
+    pub fn push_event(&mut self, event: TelemetryEvent) {
+        self.events.push_back(event);
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        let capacity = ((self.retention_ms / MIN_SAMPLE_MS) as usize).clamp(1, MAX_SAMPLES);
+        while self.samples.len() > capacity {
+            self.samples.pop_front();
+        }
+        if let Some(latest) = self.samples.back().map(|s| s.ts) {
+            while self.samples.front().map(|s| latest.saturating_sub(s.ts) > self.retention_ms).unwrap_or(false) {
+                self.samples.pop_front();
+            }
+        }
+        let now = now_ms();
+        while self.events.front().map(|e| now.saturating_sub(e.ts) > self.retention_ms).unwrap_or(false) {
+            self.events.pop_front();
+        }
+    }
+
+    pub fn oldest_ts(&self) -> Option<u64> {
+        self.samples.front().map(|s| s.ts)
+    }
+
+    pub fn range(&self, start: u64, end: u64) -> Vec<TelemetrySample> {
+        self.samples.iter().filter(|s| s.ts >= start && s.ts <= end).cloned().collect()
+    }
+
+    pub fn events(&self, start: u64, end: u64) -> Vec<TelemetryEvent> {
+        self.events.iter().filter(|e| e.ts >= start && e.ts <= end).cloned().collect()
+    }
+}
+
+impl TelemetryState {
+    // 手动改坏的配置文件按默认值处理
+    pub fn new(config: TelemetryConfig) -> Self {
+        let config = match check_telemetry_config(&config) {
+            Ok(()) => config,
+            Err(e) => {
+                println!("遥测配置无效，使用默认值: {}", e);
+                TelemetryConfig::default()
+            }
+        };
+        TelemetryState {
+            buffer: Arc::new(Mutex::new(TelemetryBuffer::new(config.retention_secs))),
+            recorder: Arc::new(Mutex::new(TelemetryRecorder::new(config))),
+            events: broadcast::channel(64).0,
+        }
+    }
+
+    pub fn record_sample(&self, sample: TelemetrySample) {
+        if let Err(e) = self.recorder.lock().unwrap().record(&TelemetryRecord::Sample(sample.clone())) {
+            println!("遥测日志写入失败: {}", e);
+        }
+        self.buffer.lock().unwrap().push(sample);
+    }
+
+    pub fn record_event(&self, kind: &str, detail: String) {
+        let event = TelemetryEvent { ts: now_ms(), kind: kind.to_string(), detail };
+        if let Err(e) = self.recorder.lock().unwrap().record(&TelemetryRecord::Event(event.clone())) {
+            println!("遥测日志写入失败: {}", e);
+        }
+        let _ = self.events.send(event.clone());
+        self.buffer.lock().unwrap().push_event(event);
+    }
+
+    // 先复制内存缓冲并释放锁，磁盘文件在锁外读取，避免阻塞控制循环写入
+    pub fn history(&self, start: u64, end: u64) -> (Vec<TelemetrySample>, Vec<TelemetryEvent>) {
+        let (oldest, mut samples, mut events) = {
+            let buffer = self.buffer.lock().unwrap();
+            (buffer.oldest_ts(), buffer.range(start, end), buffer.events(start, end))
+        };
+        let disk_end = match oldest {
+            Some(oldest) if start >= oldest => return (samples, events),
+            Some(oldest) => end.min(oldest.saturating_sub(1)),
+            None => end,
+        };
+        let files = self.recorder.lock().unwrap().range_files(start, disk_end);
+        let (mut disk_samples, mut disk_events) = read_records(&files, start, disk_end);
+        // 内存中的事件保留时长与采样一致，去掉与磁盘重复的部分
+        if let Some(last) = disk_events.last().map(|e| e.ts) {
+            events.retain(|e| e.ts > last);
+        }
+        disk_samples.append(&mut samples);
+        disk_events.append(&mut events);
+        (disk_samples, disk_events)
+    }
+}
+
+fn stat(samples: &[&TelemetrySample], f: impl Fn(&TelemetrySample) -> i64) -> TelemetryStat {
+    let values: Vec<i64> = samples.iter().map(|s| f(s)).collect();
+    TelemetryStat {
+        min: values.iter().copied().min().unwrap_or(0),
+        max: values.iter().copied().max().unwrap_or(0),
+        avg: values.iter().sum::<i64>() as f64 / values.len().max(1) as f64,
+    }
+}
+
+fn point(ts: u64, samples: &[&TelemetrySample]) -> TelemetryPoint {
+    TelemetryPoint {
+        ts,
+        count: samples.len(),
+        cpu_temp: stat(samples, |s| s.cpu_temp),
+        gpu_temp: stat(samples, |s| s.gpu_temp),
+        left_fan_speed: stat(samples, |s| s.left_fan_speed),
+        right_fan_speed: stat(samples, |s| s.right_fan_speed),
+        left_duty: stat(samples, |s| s.left_duty),
+        right_duty: stat(samples, |s| s.right_duty),
+        apc_watt: stat(samples, |s| s.apc_watt),
+        cpu_pl1: stat(samples, |s| s.cpu_pl1),
+        cpu_pl2: stat(samples, |s| s.cpu_pl2),
+        battery_percent: stat(samples, |s| s.battery_percent),
+        battery_current: stat(samples, |s| s.battery_current),
+        profile: samples.last().map(|s| s.profile.clone()).unwrap_or_default(),
+    }
+}
+
+// 按 resolution_ms 分桶计算 min/max/avg，resolution_ms 为 0 时返回原始采样
+pub fn downsample(samples: &[TelemetrySample], start: u64, resolution_ms: u64) -> Vec<TelemetryPoint> {
+    if resolution_ms == 0 {
+        return samples.iter().map(|s| point(s.ts, &[s])).collect();
+    }
+    let mut points = Vec::new();
+    let mut bucket: Vec<&TelemetrySample> = Vec::new();
+    let mut bucket_ts = 0;
+    for sample in samples {
+        let ts = start + (sample.ts.saturating_sub(start) / resolution_ms) * resolution_ms;
+        if !bucket.is_empty() && ts != bucket_ts {
+            points.push(point(bucket_ts, &bucket));
+            bucket.clear();
+        }
+        bucket_ts = ts;
+        bucket.push(sample);
+    }
+    if !bucket.is_empty() {
+        points.push(point(bucket_ts, &bucket));
+    }
+    points
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn sample(ts: u64, cpu_temp: i64, profile: &str) -> TelemetrySample {
+        TelemetrySample {
+            ts,
+            cpu_temp,
+            gpu_temp: cpu_temp - 10,
+            left_fan_speed: 0,
+            right_fan_speed: 0,
+            left_duty: 0,
+            right_duty: 0,
+            profile: profile.to_string(),
+            apc_watt: 0,
+            cpu_pl1: 0,
+            cpu_pl2: 0,
+            battery_percent: 0,
+            battery_current: 0,
+        }
+    }
+
+    fn samples() -> Vec<TelemetrySample> {
+        vec![
+            sample(1000, 40, "quiet"),
+            sample(1400, 50, "custom"),
+            sample(2100, 60, "custom"),
+            sample(2900, 70, "custom"),
+            sample(4000, 81, "auto"),
+        ]
+    }
+
+    #[test]
+    fn downsample_buckets_by_resolution() {
+        let points = downsample(&samples(), 1000, 1000);
+        // 没有采样的区间不输出
+        let ts: Vec<u64> = points.iter().map(|p| p.ts).collect();
+        assert_eq!(ts, [1000, 2000, 4000]);
+        assert_eq!(points.iter().map(|p| p.count).collect::<Vec<_>>(), [2, 2, 1]);
+        let first = &points[0];
+        assert_eq!((first.cpu_temp.min, first.cpu_temp.max, first.cpu_temp.avg), (40, 50, 45.0));
+        assert_eq!((first.gpu_temp.min, first.gpu_temp.max, first.gpu_temp.avg), (30, 40, 35.0));
+        // 配置名取区间内最后一次采样
+        assert_eq!(first.profile, "custom");
+        assert_eq!((points[2].cpu_temp.min, points[2].cpu_temp.max, points[2].cpu_temp.avg), (81, 81, 81.0));
+        assert_eq!(points[2].profile, "auto");
+    }
+
+    #[test]
+    fn downsample_aligns_buckets_to_start() {
+        let points = downsample(&samples(), 900, 1000);
+        let ts: Vec<u64> = points.iter().map(|p| p.ts).collect();
+        assert_eq!(ts, [900, 1900, 2900, 3900]);
+        assert_eq!(points.iter().map(|p| p.count).collect::<Vec<_>>(), [2, 1, 1, 1]);
+    }
+
+    #[test]
+    fn zero_resolution_returns_raw_samples() {
+        let points = downsample(&samples(), 0, 0);
+        assert_eq!(points.len(), 5);
+        for (point, sample) in points.iter().zip(samples()) {
+            assert_eq!((point.ts, point.count), (sample.ts, 1));
+            assert_eq!((point.cpu_temp.min, point.cpu_temp.max, point.cpu_temp.avg), (sample.cpu_temp, sample.cpu_temp, sample.cpu_temp as f64));
+            assert_eq!(point.profile, sample.profile);
+        }
+        assert!(downsample(&[], 0, 1000).is_empty());
+    }
+
+    #[test]
+    fn buffer_evicts_samples_older_than_retention() {
+        let mut buffer = TelemetryBuffer::new(10);
+        for ts in (0..=20000).step_by(1000) {
+            buffer.push(sample(ts, 50, "custom"));
+        }
+        // 按最新一次采样计算保留时长
+        assert_eq!(buffer.oldest_ts(), Some(10000));
+        assert_eq!(buffer.range(0, u64::MAX).len(), 11);
+        assert_eq!(buffer.range(12000, 14000).len(), 3);
+        buffer.set_retention(5);
+        assert_eq!(buffer.oldest_ts(), Some(15000));
+    }
+
+    #[test]
+    fn buffer_caps_sample_count() {
+        // 10s 按最短采样间隔最多 100 个采样
+        let mut buffer = TelemetryBuffer::new(10);
+        for ts in (0..150).map(|i| i * 10) {
+            buffer.push(sample(ts, 50, "custom"));
+        }
+        assert_eq!(buffer.range(0, u64::MAX).len(), 100);
+        assert_eq!(buffer.oldest_ts(), Some(500));
+    }
+
+    #[test]
+    fn buffer_evicts_events_by_wall_clock() {
+        let mut buffer = TelemetryBuffer::new(10);
+        let now = now_ms();
+        buffer.push_event(TelemetryEvent { ts: now - 20000, kind: "start".to_string(), detail: String::new() });
+        buffer.push_event(TelemetryEvent { ts: now, kind: "stage".to_string(), detail: String::new() });
+        let events = buffer.events(0, u64::MAX);
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, "stage");
+    }
+
+    fn event(ts: u64, kind: &str) -> TelemetryEvent {
+        TelemetryEvent { ts, kind: kind.to_string(), detail: String::new() }
+    }
+
+    // 磁盘上有 base+1000~base+5000 的记录，内存只保留 base+4000 之后的采样
+    fn state_with_history(name: &str, base: u64) -> (TelemetryState, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!("nuc-fan-history-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let config = TelemetryConfig { record_to_disk: true, ..TelemetryConfig::default() };
+        let mut recorder = TelemetryRecorder::with_dir(dir.clone(), config);
+        let mut buffer = TelemetryBuffer::new(3600);
+        for ts in (1000..=5000).step_by(500) {
+            recorder.record(&TelemetryRecord::Sample(sample(base + ts, 50, "custom"))).unwrap();
+            if ts >= 4000 {
+                buffer.push(sample(base + ts, 50, "custom"));
+            }
+        }
+        for (ts, kind) in [(1500, "start"), (3500, "stage"), (4500, "stage")] {
+            recorder.record(&TelemetryRecord::Event(event(base + ts, kind))).unwrap();
+        }
+        // 事件按时间保留，比内存中最早的采样更早的事件在磁盘上也有一份
+        for (ts, kind) in [(3500, "stage"), (4500, "stage"), (4800, "stop")] {
+            buffer.push_event(event(base + ts, kind));
+        }
+        let state = TelemetryState {
+            buffer: Arc::new(Mutex::new(buffer)),
+            recorder: Arc::new(Mutex::new(recorder)),
+            events: broadcast::channel(4).0,
+        };
+        (state, dir)
+    }
+
+    #[test]
+    fn history_reads_disk_before_memory() {
+        let base = now_ms() - 60000;
+        let (state, dir) = state_with_history("disk", base);
+        let (samples, events) = state.history(base, base + 6000);
+        let ts: Vec<u64> = samples.iter().map(|s| s.ts - base).collect();
+        // 磁盘只读到内存最早采样之前，不重复
+        assert_eq!(ts, (1000..=5000).step_by(500).collect::<Vec<u64>>());
+        let ts: Vec<u64> = events.iter().map(|e| e.ts - base).collect();
+        assert_eq!(ts, [1500, 3500, 4500, 4800]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn history_within_memory_skips_disk() {
+        let base = now_ms() - 60000;
+        let (state, dir) = state_with_history("memory", base);
+        // 删除磁盘日志后仍能从内存返回
+        fs::remove_dir_all(&dir).unwrap();
+        let (samples, events) = state.history(base + 4200, base + 6000);
+        assert_eq!(samples.iter().map(|s| s.ts - base).collect::<Vec<_>>(), [4500, 5000]);
+        assert_eq!(events.iter().map(|e| e.ts - base).collect::<Vec<_>>(), [4500, 4800]);
+    }
+
+    #[test]
+    fn telemetry_config_limits() {
+        assert!(check_telemetry_config(&TelemetryConfig::default()).is_ok());
+        for config in [
+            TelemetryConfig { retention_secs: 0, ..TelemetryConfig::default() },
+            TelemetryConfig { max_file_kb: 0, ..TelemetryConfig::default() },
+            TelemetryConfig { max_total_mb: 0, ..TelemetryConfig::default() },
+            TelemetryConfig { max_age_hours: 0, ..TelemetryConfig::default() },
+            TelemetryConfig { max_total_mb: u64::MAX, ..TelemetryConfig::default() },
+            TelemetryConfig { max_age_hours: u64::MAX, ..TelemetryConfig::default() },
+            TelemetryConfig { retention_secs: u64::MAX, ..TelemetryConfig::default() },
+            // 总大小小于单个文件
+            TelemetryConfig { max_file_kb: 4096, max_total_mb: 2, ..TelemetryConfig::default() },
+        ] {
+            assert!(check_telemetry_config(&config).is_err());
+        }
+    }
+}
@@ -0,0 +1,361 @@
+use serde::{Deserialize, Serialize};
+use crate::modules::config::{load_vendor_fan_config, save_vendor_fan_config};
+use crate::modules::device::{current_capability, reg_1926_is_l1};
+use crate::modules::ec::{ec_read, ec_write_all, Ec};
+use crate::modules::fan::{fan_reset, read_fan_mode, FanMode};
+use crate::modules::poll::OVERHEAT_TEMP;
+use crate::modules::profile::load_profile;
+use crate::modules::struct_set::{FanData, FanPoint, VendorFanSettings};
+
+const ADDR_MYFANCCI_MODE_INDEX: u16 = 1963;
+const ADDR_MYFANCCI_MODE_PROFILE1: u16 = 1968;
+// 分析.md 中 1926 同时是 ADDR_TCC_OFFSET，机型条目 reg_1926 为 myfan3_l1 时才写入
+const ADDR_L1_PWM_DEFAULT_MYFAN3: u16 = 1926;
+const ADDR_MYFANI_MIN_SPEED: u16 = 1950;
+const ADDR_MYFANI_MIN_TEMP: u16 = 1951;
+const ADDR_MYFANI_EXTRA_SPEED: u16 = 1952;
+
+// 分析.md 中的 ADDR_MAFAN_CONTROL_BYTE(1873) 就是 0x751 风扇控制模式寄存器，
+// 固件风扇不单独开关，EC 处于自动控制时即按这里写入的模式和五档表调速
+const MODE_DEFAULT: u8 = 0;
+
+const MAX_MODE: u8 = 3;
+
+// PWM 原始值与占空比寄存器相同，按百分比 * 2 计
+const PWM_PER_PERCENT: f64 = 2.0;
+// 估算误差的最低温度
+const FIT_MIN_TEMP: i64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct VendorFan {
+    // 0x751 为 EC 自动控制且选择了非默认模式
+    pub enabled: bool,
+    pub mode: u8,
+    // Profile1~3 中保存的模式值
+    pub profiles: [u8; 3],
+    pub curve: [u8; 5],
+    pub min_speed: u8,
+    pub min_temp: u8,
+    pub extra_speed: u8,
+    // 为 false 时 1926 按 TCC 偏移处理，curve[0] 读出的是温度墙偏移，写入时跳过
+    pub l1_writable: bool,
+}
+
+pub fn read_vendor_fan(ec: &dyn Ec) -> VendorFan {
+    let read = |addr: u16| ec_read(ec, addr) as u8;
+    VendorFan {
+        enabled: read_fan_mode(ec) == FanMode::Auto && read(ADDR_MYFANCCI_MODE_INDEX) != MODE_DEFAULT,
+        mode: read(ADDR_MYFANCCI_MODE_INDEX),
+        profiles: [0, 1, 2].map(|i| read(ADDR_MYFANCCI_MODE_PROFILE1 + i)),
+        curve: [0, 1, 2, 3, 4].map(|i| read(ADDR_L1_PWM_DEFAULT_MYFAN3 + i)),
+        min_speed: read(ADDR_MYFANI_MIN_SPEED),
+        min_temp: read(ADDR_MYFANI_MIN_TEMP),
+        extra_speed: read(ADDR_MYFANI_EXTRA_SPEED),
+        l1_writable: reg_1926_is_l1(),
+    }
+}
+
+pub fn check_vendor_fan(settings: &VendorFanSettings) -> Result<(), String> {
+    if settings.mode > MAX_MODE {
+        return Err(format!("mode 超出范围 0~{}: {}", MAX_MODE, settings.mode));
+    }
+    if settings.curve.windows(2).any(|w| w[0] > w[1]) {
+        return Err(format!("五档 PWM 应逐档不减: {:?}", settings.curve));
+    }
+    Ok(())
+}
+
+// 1926 不是 L1 时不写入，曲线的 L1 必须与其当前值一致，否则写入的表和实际表不同
+fn check_l1_with(ec: &dyn Ec, settings: &VendorFanSettings, l1_writable: bool) -> Result<(), String> {
+    let current = ec_read(ec, ADDR_L1_PWM_DEFAULT_MYFAN3) as u8;
+    if !l1_writable && settings.curve[0] != current {
+        return Err(format!(
+            "1926 在当前机型上是 TCC 偏移，不能写入 L1: 当前值 {}，曲线 L1 {}；确认为 L1 后在 device_config.json 中设置 reg_1926",
+            current, settings.curve[0]
+        ));
+    }
+    Ok(())
+}
+
+pub fn check_l1(ec: &dyn Ec, settings: &VendorFanSettings) -> Result<(), String> {
+    check_l1_with(ec, settings, reg_1926_is_l1())
+}
+
+// 先写曲线和参数，最后把 0x751 交还 EC 自动控制，EC 切换时直接使用新曲线
+fn write_vendor_fan(ec: &dyn Ec, settings: &VendorFanSettings, l1_writable: bool) -> Result<(), String> {
+    check_vendor_fan(settings)?;
+    let mut writes: Vec<(u16, u8)> = settings
+        .curve
+        .iter()
+        .enumerate()
+        .skip(if l1_writable { 0 } else { 1 })
+        .map(|(i, &pwm)| (ADDR_L1_PWM_DEFAULT_MYFAN3 + i as u16, pwm))
+        .collect();
+    writes.extend([
+        (ADDR_MYFANI_MIN_SPEED, settings.min_speed),
+        (ADDR_MYFANI_MIN_TEMP, settings.min_temp),
+        (ADDR_MYFANI_EXTRA_SPEED, settings.extra_speed),
+        (ADDR_MYFANCCI_MODE_INDEX, settings.mode),
+    ]);
+    ec_write_all(ec, &writes).check()?;
+    fan_reset(ec);
+    Ok(())
+}
+
+// 保存后由 apply_vendor_fan_config 在启动时重写；写入会交还 EC 自动控制，调用方需先停止软件调速
+pub fn set_vendor_fan(ec: &dyn Ec, settings: &VendorFanSettings) -> Result<(), String> {
+    let l1_writable = reg_1926_is_l1();
+    check_l1_with(ec, settings, l1_writable)?;
+    write_vendor_fan(ec, settings, l1_writable)?;
+    let mut config = load_vendor_fan_config();
+    config.settings = Some(*settings);
+    save_vendor_fan_config(&config)
+}
+
+// 恢复固件默认模式，0x751 保持不变
+pub fn disable_vendor_fan(ec: &dyn Ec) -> Result<(), String> {
+    ec_write_all(ec, &[(ADDR_MYFANCCI_MODE_INDEX, MODE_DEFAULT)]).check()?;
+    let mut config = load_vendor_fan_config();
+    config.settings = None;
+    save_vendor_fan_config(&config)
+}
+
+// 1926 不是 L1 时跳过 L1，保存后 TCC 偏移被修改也不会在启动时被曲线覆盖
+pub fn apply_vendor_fan_config(ec: &dyn Ec) {
+    if let Some(settings) = load_vendor_fan_config().settings {
+        match write_vendor_fan(ec, &settings, reg_1926_is_l1()) {
+            Ok(()) => println!("已恢复固件风扇模式 {}", settings.mode),
+            Err(e) => println!("vendor_fan_config.json: {}", e),
+        }
+    }
+}
+
+// 曲线转换为固件表后的结果，误差单位为转速百分比
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FanTableFit {
+    pub profile: String,
+    pub settings: VendorFanSettings,
+    // 五档 PWM 对应的温度
+    pub temps: [i64; 5],
+    // 机型条目没有给出 fan_breakpoints 时温度点是假设的，误差也只是按假设估算
+    pub temps_assumed: bool,
+    // 1926 不是 L1 的机型上只能预览，不能写入
+    pub l1_writable: bool,
+    pub max_error: f64,
+    pub max_error_temp: i64,
+    pub mean_error: f64,
+}
+
+// 固件表的五档温度点未公开，机型条目没有给出时按 min_temp 到过热温度等分估算
+pub fn table_temps(min_temp: u8, breakpoints: Option<[u8; 5]>) -> [i64; 5] {
+    if let Some(breakpoints) = breakpoints {
+        return breakpoints.map(|t| t as i64);
+    }
+    let min_temp = min_temp as i64;
+    [0, 1, 2, 3, 4].map(|i| min_temp + (OVERHEAT_TEMP - min_temp) * i / 4)
+}
+
+fn current_breakpoints() -> Option<[u8; 5]> {
+    current_capability().and_then(|c| c.fan_breakpoints)
+}
+
+// 曲线在某温度的转速(%)，两点之间线性插值，超出两端取端点值
+fn curve_speed(points: &[FanPoint], temp: i64) -> f64 {
+    let (Some(first), Some(last)) = (points.first(), points.last()) else {
+        return 0.0;
+    };
+    if temp <= first.temperature as i64 {
+        return first.speed as f64;
+    }
+    for w in points.windows(2) {
+        let (t0, t1) = (w[0].temperature as i64, w[1].temperature as i64);
+        if temp <= t1 {
+            let k = (temp - t0) as f64 / (t1 - t0).max(1) as f64;
+            return w[0].speed as f64 + (w[1].speed - w[0].speed) as f64 * k;
+        }
+    }
+    last.speed as f64
+}
+
+// 固件表只有一条曲线，左右风扇取两条曲线中较高的转速
+fn target_speed(data: &FanData, temp: i64) -> f64 {
+    curve_speed(&data.left_fan, temp).max(curve_speed(&data.right_fan, temp))
+}
+
+// 固件表在某温度的转速(%)，低于 min_temp 按最低转速，五档之间按线性估算
+fn table_speed(settings: &VendorFanSettings, breakpoints: Option<[u8; 5]>, temp: i64) -> f64 {
+    let temps = table_temps(settings.min_temp, breakpoints);
+    if temp < temps[0] {
+        return settings.min_speed as f64 / PWM_PER_PERCENT;
+    }
+    let points: Vec<FanPoint> = temps
+        .iter()
+        .zip(settings.curve)
+        .map(|(&t, pwm)| FanPoint { temperature: t as i32, speed: (pwm as f64 / PWM_PER_PERCENT) as i32 })
+        .collect();
+    curve_speed(&points, temp)
+}
+
+fn to_pwm(speed: f64) -> u8 {
+    (speed * PWM_PER_PERCENT).round().clamp(0.0, 200.0) as u8
+}
+
+// 把 FanData 曲线换算为最接近的固件五档表；mode 和 extra_speed 原样写入
+pub fn fit_fan_table(data: &FanData, mode: u8, extra_speed: u8, breakpoints: Option<[u8; 5]>) -> Result<VendorFanSettings, String> {
+    if let Some(b) = breakpoints.filter(|b| b.windows(2).any(|w| w[0] >= w[1])) {
+        return Err(format!("fan_breakpoints 应逐档递增: {:?}", b));
+    }
+    let temps: Vec<i64> = data.left_fan.iter().chain(&data.right_fan).map(|p| p.temperature as i64).collect();
+    let lowest = temps.iter().min().ok_or("曲线为空")?;
+    // 风扇开始转动的温度作为 min_temp，留出五档的温度区间
+    let start = (*lowest..OVERHEAT_TEMP).find(|&t| target_speed(data, t) > 0.0).unwrap_or(*lowest);
+    let min_temp = start.clamp(FIT_MIN_TEMP, OVERHEAT_TEMP - 20) as u8;
+    let mut curve = table_temps(min_temp, breakpoints).map(|t| to_pwm(target_speed(data, t)));
+    // 固件要求逐档不减
+    for i in 1..curve.len() {
+        curve[i] = curve[i].max(curve[i - 1]);
+    }
+    let settings = VendorFanSettings {
+        mode,
+        curve,
+        min_speed: to_pwm(target_speed(data, min_temp as i64 - 1)),
+        min_temp,
+        extra_speed,
+    };
+    check_vendor_fan(&settings)?;
+    Ok(settings)
+}
+
+pub fn fan_table_error(name: &str, data: &FanData, settings: &VendorFanSettings, breakpoints: Option<[u8; 5]>) -> FanTableFit {
+    let (mut max_error, mut max_error_temp, mut total) = (0.0f64, FIT_MIN_TEMP, 0.0);
+    for temp in FIT_MIN_TEMP..=OVERHEAT_TEMP {
+        let error = (target_speed(data, temp) - table_speed(settings, breakpoints, temp)).abs();
+        total += error;
+        if error > max_error {
+            (max_error, max_error_temp) = (error, temp);
+        }
+    }
+    FanTableFit {
+        profile: name.to_string(),
+        settings: *settings,
+        temps: table_temps(settings.min_temp, breakpoints),
+        temps_assumed: breakpoints.is_none(),
+        l1_writable: reg_1926_is_l1(),
+        max_error,
+        max_error_temp,
+        mean_error: total / (OVERHEAT_TEMP - FIT_MIN_TEMP + 1) as f64,
+    }
+}
+
+// 只计算不写入，用于预览转换效果
+pub fn preview_fan_table(ec: &dyn Ec, name: &str, mode: u8) -> Result<FanTableFit, String> {
+    let data = load_profile(name)?;
+    let breakpoints = current_breakpoints();
+    let settings = fit_fan_table(&data, mode, ec_read(ec, ADDR_MYFANI_EXTRA_SPEED) as u8, breakpoints)?;
+    Ok(fan_table_error(name, &data, &settings, breakpoints))
+}
+
+// 写入固件表并保存，误差按 EC 读回的表计算；调用方需先停止软件调速
+// 整张表都由曲线换算，1926 不是 L1 的机型上无法写入
+pub fn program_fan_table(ec: &dyn Ec, name: &str, mode: u8) -> Result<FanTableFit, String> {
+    if !reg_1926_is_l1() {
+        return Err("1926 在当前机型上是 TCC 偏移，无法写入固件风扇表 L1；确认为 L1 后在 device_config.json 中设置 reg_1926".to_string());
+    }
+    let data = load_profile(name)?;
+    let breakpoints = current_breakpoints();
+    let settings = fit_fan_table(&data, mode, ec_read(ec, ADDR_MYFANI_EXTRA_SPEED) as u8, breakpoints)?;
+    set_vendor_fan(ec, &settings)?;
+    let current = read_vendor_fan(ec);
+    let read_back = VendorFanSettings {
+        mode: current.mode,
+        curve: current.curve,
+        min_speed: current.min_speed,
+        min_temp: current.min_temp,
+        extra_speed: current.extra_speed,
+    };
+    if read_fan_mode(ec) != FanMode::Auto || read_back != settings {
+        return Err(format!("固件风扇表校验失败: 写入 {:?}，读回 {:?}", settings, read_back));
+    }
+    println!("已把配置 {} 写入固件风扇表 {:?}", name, settings.curve);
+    Ok(fan_table_error(name, &data, &read_back, breakpoints))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::modules::sim::{SimEc, SimState};
+
+    const TCC: u8 = 7;
+
+    fn settings(l1: u8) -> VendorFanSettings {
+        VendorFanSettings { mode: 1, curve: [l1, 64, 128, 192, 200], min_speed: 20, min_temp: 30, extra_speed: 0 }
+    }
+
+    fn sim() -> SimEc {
+        let mut state = SimState::new();
+        state.set_reg(ADDR_L1_PWM_DEFAULT_MYFAN3, TCC);
+        SimEc::new(state)
+    }
+
+    fn points(points: &[(i32, i32)]) -> Vec<FanPoint> {
+        points.iter().map(|&(temperature, speed)| FanPoint { temperature, speed }).collect()
+    }
+
+    // 左侧 30~60℃ 由 20% 线性升到 80% 后保持，右侧始终更低，固件表按左侧拟合
+    fn knee() -> FanData {
+        FanData { left_fan: points(&[(30, 20), (60, 80), (90, 80)]), right_fan: points(&[(30, 10), (90, 50)]) }
+    }
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn fit_with_device_breakpoints() {
+        let breakpoints = Some([30, 50, 70, 90, 95]);
+        let settings = fit_fan_table(&knee(), 1, 0, breakpoints).unwrap();
+        assert_eq!(settings.curve, [40, 120, 160, 160, 160]);
+        assert_eq!((settings.min_temp, settings.min_speed), (30, 40));
+        let fit = fan_table_error("knee", &knee(), &settings, breakpoints);
+        assert_eq!(fit.temps, [30, 50, 70, 90, 95]);
+        assert!(!fit.temps_assumed);
+        // 51~60℃ 误差 1~10，61~69℃ 误差 9~1，合计 100
+        assert_close(fit.max_error, 10.0);
+        assert_eq!(fit.max_error_temp, 60);
+        assert_close(fit.mean_error, 100.0 / 66.0);
+    }
+
+    #[test]
+    fn fit_with_assumed_breakpoints() {
+        let settings = fit_fan_table(&knee(), 1, 0, None).unwrap();
+        assert_eq!(settings.curve, [40, 104, 160, 160, 160]);
+        let fit = fan_table_error("knee", &knee(), &settings, None);
+        assert_eq!(fit.temps, [30, 46, 62, 78, 95]);
+        assert!(fit.temps_assumed);
+        // 47~60℃ 误差 0.25~3.5，61℃ 误差 1.75，合计 28
+        assert_close(fit.max_error, 3.5);
+        assert_eq!(fit.max_error_temp, 60);
+        assert_close(fit.mean_error, 28.0 / 66.0);
+        assert!(fit_fan_table(&knee(), 1, 0, Some([30, 50, 50, 90, 95])).is_err());
+    }
+
+    #[test]
+    fn tcc_offset_is_not_written_as_l1() {
+        let ec = sim();
+        assert!(check_l1_with(&ec, &settings(40), false).is_err());
+        check_l1_with(&ec, &settings(TCC), false).unwrap();
+        write_vendor_fan(&ec, &settings(40), false).unwrap();
+        let state = ec.state().lock().unwrap();
+        assert_eq!(state.reg(ADDR_L1_PWM_DEFAULT_MYFAN3), TCC);
+        assert_eq!(state.reg(ADDR_L1_PWM_DEFAULT_MYFAN3 + 1), 64);
+        assert_eq!(state.reg(ADDR_MYFANCCI_MODE_INDEX), 1);
+    }
+
+    #[test]
+    fn confirmed_l1_is_written() {
+        let ec = sim();
+        check_l1_with(&ec, &settings(40), true).unwrap();
+        write_vendor_fan(&ec, &settings(40), true).unwrap();
+        assert_eq!(ec.state().lock().unwrap().reg(ADDR_L1_PWM_DEFAULT_MYFAN3), 40);
+    }
+}
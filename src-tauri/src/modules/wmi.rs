@@ -11,6 +11,13 @@ pub fn wmi_security() {
     }
 }
 
+// 在新线程中使用 WMI 前初始化 COM，进程级安全设置仍由 wmi_security 完成
+pub fn wmi_thread_init() {
+    unsafe {
+        let _ = CoInitializeEx(None, COINIT_MULTITHREADED);
+    }
+}
+
 pub fn wmi_init() -> (IWbemClassObject, IWbemServices, BSTR, BSTR){
 
     // Connect to the required namespace on the local DCOM server.
@@ -63,28 +70,31 @@ pub fn wmi_init() -> (IWbemClassObject, IWbemServices, BSTR, BSTR){
     (in_cls.unwrap(), svc, obj_path, method_name)
 }
 pub fn wmi_set(in_cls: &IWbemClassObject, svc: &IWbemServices, obj_path: &BSTR, method_name: &BSTR, size: &str) -> i64 {
+    wmi_try_set(in_cls, svc, obj_path, method_name, size).unwrap()
+}
+
+// 与 wmi_set 相同，失败时返回错误而不是 panic
+pub fn wmi_try_set(in_cls: &IWbemClassObject, svc: &IWbemServices, obj_path: &BSTR, method_name: &BSTR, size: &str) -> anyhow::Result<i64> {
     let in_params =
-        unsafe { in_cls.SpawnInstance(0).context("Creating input params").unwrap() };
+        unsafe { in_cls.SpawnInstance(0).context("Creating input params")? };
 
     // Set the desired parameters on the input parameter object.
     // let size =  0x0000000000C01809u64.to_string();
+    let data = u64::from_str_radix(size.trim_start_matches("0x"), 16).context("Parsing data")?;
     unsafe {
-        // in_params.Put(&BSTR::from("AssignDriveLetter"), 0, &VARIANT::from(true), 0).context("Setting AssignDriveLetter")?;
-        in_params.Put(&BSTR::from("Data"), 0, &VARIANT::from(u64::from_str_radix(&size[2..], 16).unwrap().to_string().as_str()), 0).context("Setting Size").unwrap();
+        in_params.Put(&BSTR::from("Data"), 0, &VARIANT::from(data.to_string().as_str()), 0).context("Setting Size")?;
     }
 
     // Call the method and check the return value.
-    // println!("Calling method with {}", unsafe { in_params.GetObjectText(0).unwrap() });
     let mut out_params: Option<IWbemClassObject> = None;
     unsafe {
         svc.ExecMethod(obj_path, method_name, WBEM_FLAG_RETURN_WBEM_COMPLETE, None, &in_params, Some(&mut out_params), None)
-            .context("Failed to call CreatePartition").unwrap();
+            .context("Failed to call GetSetULong")?;
     }
-    let out_params = out_params.ok_or_else(|| anyhow!("Missing output parameters")).unwrap();
+    let out_params = out_params.ok_or_else(|| anyhow!("Missing output parameters"))?;
     let mut return_value = VARIANT::new();
     unsafe {
-        out_params.Get(w!("Return"), 0, &mut return_value, None, None).unwrap();
+        out_params.Get(w!("Return"), 0, &mut return_value, None, None).context("Getting return value")?;
     }
-    // println!("Return value = {return_value}.");
-    return_value.to_string().parse::<i64>().unwrap()
-}
\ No newline at end of file
+    return_value.to_string().parse::<i64>().context("Parsing return value")
+}
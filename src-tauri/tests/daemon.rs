@@ -0,0 +1,85 @@
+#![cfg(unix)]
+
+use std::fs;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use x15_71fan::modules::ipc::IpcClient;
+
+const PROFILE: &str = r#"{
+    "left_fan": [{"temperature": 30, "speed": 20}, {"temperature": 100, "speed": 100}],
+    "right_fan": [{"temperature": 30, "speed": 20}, {"temperature": 100, "speed": 100}]
+}"#;
+
+// 已启用固件风扇，恢复时会把 0x751 交还 EC
+const VENDOR_FAN: &str = r#"{
+    "settings": { "mode": 1, "curve": [40, 64, 128, 192, 200], "min_speed": 20, "min_temp": 30, "extra_speed": 0 }
+}"#;
+
+fn write_config(root: &Path) {
+    let dir = root.join("config").join("com.nuc.x15.fan.cyear.app");
+    fs::create_dir_all(dir.join("profiles")).unwrap();
+    fs::write(dir.join("profiles").join("test.json"), PROFILE).unwrap();
+    fs::write(dir.join("vendor_fan_config.json"), VENDOR_FAN).unwrap();
+}
+
+fn connect(child: &mut Child) -> IpcClient {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Ok(client) = IpcClient::connect() {
+            return client;
+        }
+        if let Some(status) = child.try_wait().unwrap() {
+            panic!("nuc-fan-daemon 提前退出: {}", status);
+        }
+        assert!(Instant::now() < deadline, "连接 nuc-fan-daemon 超时");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+// 控制循环先于配置启动，第一次采样可能还是 auto，等待按配置调速后的采样
+fn wait_snapshot(client: &mut IpcClient, profile: &str) -> Value {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let status = client.call("status", Value::Null).unwrap();
+        if status["snapshot"]["profile"] == json!(profile) {
+            return status;
+        }
+        assert!(Instant::now() < deadline, "没有收到采样快照");
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+#[test]
+fn daemon_serves_status_over_socket() {
+    let root = std::env::temp_dir().join(format!("nuc-fan-daemon-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    write_config(&root);
+    let socket = root.join("nuc-fan.sock");
+    std::env::set_var("NUC_FAN_SOCKET", &socket);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_nuc-fan-daemon"))
+        .args(["--sim", "--profile", "test"])
+        .env("XDG_CONFIG_HOME", root.join("config"))
+        .env("NUC_FAN_SOCKET", &socket)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut client = connect(&mut child);
+    client.check_version().unwrap();
+
+    let status = wait_snapshot(&mut client, "test");
+    assert_eq!(status["running"], json!(true));
+    assert_eq!(status["profile"], json!("test"));
+    // 恢复固件风扇设置后再启动配置，最终处于手动控制
+    let mode = client.call("fan.mode", Value::Null).unwrap();
+    assert_eq!(mode["mode"], json!("manual"));
+    assert_eq!(mode["state"], json!("controlling"));
+
+    Command::new("kill").args(["-TERM", &child.id().to_string()]).status().unwrap();
+    assert!(child.wait().unwrap().success());
+    let _ = fs::remove_dir_all(&root);
+}
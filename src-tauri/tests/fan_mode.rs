@@ -0,0 +1,67 @@
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::json;
+use x15_71fan::modules::{
+    control::Controller,
+    device::device_init,
+    ec::{ec_init, ec_open, ec_read, use_simulator},
+    fan::{fan_mode_state, fan_reset, fan_set, FanModeState},
+    sim::SimEc,
+    struct_set::PollConfig,
+};
+
+const ADDR_LEFT_DUTY: u16 = 0x1809;
+const FAN_MODE_AUTO: u8 = 0x10;
+
+fn wait_until(what: &str, f: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !f() {
+        assert!(Instant::now() < deadline, "等待超时: {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+// EC 一直不接受手动模式时，恢复失败后停止调速并在快照中报告
+#[test]
+fn failed_recovery_stops_control_loop() {
+    let root = std::env::temp_dir().join(format!("nuc-fan-mode-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &root);
+    use_simulator();
+    ec_init();
+    device_init(ec_open().as_ref(), false).unwrap();
+
+    let controller = Controller::new();
+    *controller.poll.config.lock().unwrap() = PollConfig { interval_ms: 20, ..PollConfig::default() };
+    let profile = json!({
+        "left_fan": [{"temperature": 0, "speed": 40}, {"temperature": 120, "speed": 40}],
+        "right_fan": [{"temperature": 0, "speed": 40}, {"temperature": 120, "speed": 40}]
+    });
+    let ec = ec_open();
+    controller.start(ec.as_ref(), "test", profile);
+    controller.spawn(|_| {});
+    wait_until("曲线调速", || ec_read(ec.as_ref(), ADDR_LEFT_DUTY) == 80);
+    assert_eq!(fan_mode_state(), FanModeState::Controlling);
+
+    SimEc::shared().state().lock().unwrap().set_fan_mode_stuck(Some(FAN_MODE_AUTO));
+    wait_until("恢复失败", || controller.latest().map(|s| s.faults.iter().any(|f| f == "fan_mode_failed")).unwrap_or(false));
+    assert_eq!(fan_mode_state(), FanModeState::Failed);
+    assert!(!controller.is_running());
+    let snapshot = controller.latest().unwrap();
+    assert!(!snapshot.running);
+    assert_eq!((snapshot.sample.left_duty, snapshot.sample.right_duty), (0, 0));
+
+    // 失败后不再写占空比寄存器
+    SimEc::shared().state().lock().unwrap().set_reg(ADDR_LEFT_DUTY, 0);
+    assert!(fan_set(ec.as_ref(), 50, 50).is_err());
+    thread::sleep(Duration::from_millis(100));
+    assert_eq!(ec_read(ec.as_ref(), ADDR_LEFT_DUTY), 0);
+
+    // 交还自动控制后故障消失
+    SimEc::shared().state().lock().unwrap().set_fan_mode_stuck(None);
+    fan_reset(ec.as_ref());
+    wait_until("故障清除", || controller.latest().map(|s| s.faults.iter().all(|f| f != "fan_mode_failed")).unwrap_or(false));
+    let _ = fs::remove_dir_all(&root);
+}
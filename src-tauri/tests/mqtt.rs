@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use serde_json::{json, Value};
+use x15_71fan::modules::{
+    control::Controller,
+    device::device_init,
+    ec::{ec_init, ec_open, ec_read, use_simulator},
+    fan::{fan_mode_state, FanModeState},
+    mqtt::start_mqtt,
+    struct_set::MqttConfig,
+};
+
+const PROFILE: &str = r#"{
+    "left_fan": [{"temperature": 30, "speed": 20}, {"temperature": 100, "speed": 100}],
+    "right_fan": [{"temperature": 30, "speed": 20}, {"temperature": 100, "speed": 100}]
+}"#;
+
+const ADDR_LEFT_DUTY: u16 = 0x1809;
+const ADDR_RIGHT_DUTY: u16 = 0x1804;
+
+struct Packet {
+    kind: u8,
+    flags: u8,
+    body: Vec<u8>,
+}
+
+// 只实现 MQTT 3.1.1 中客户端会用到的部分
+struct BrokerStub {
+    stream: TcpStream,
+}
+
+impl BrokerStub {
+    fn read_packet(&mut self) -> Packet {
+        let mut header = [0u8; 1];
+        self.stream.read_exact(&mut header).unwrap();
+        let (mut len, mut shift) = (0usize, 0);
+        loop {
+            let mut byte = [0u8; 1];
+            self.stream.read_exact(&mut byte).unwrap();
+            len |= ((byte[0] & 0x7F) as usize) << shift;
+            if byte[0] & 0x80 == 0 {
+                break;
+            }
+            shift += 7;
+        }
+        let mut body = vec![0u8; len];
+        self.stream.read_exact(&mut body).unwrap();
+        Packet { kind: header[0] >> 4, flags: header[0] & 0x0F, body }
+    }
+
+    fn send(&mut self, header: u8, body: &[u8]) {
+        let mut packet = vec![header];
+        let mut len = body.len();
+        loop {
+            let byte = (len % 128) as u8;
+            len /= 128;
+            packet.push(if len > 0 { byte | 0x80 } else { byte });
+            if len == 0 {
+                break;
+            }
+        }
+        packet.extend_from_slice(body);
+        self.stream.write_all(&packet).unwrap();
+    }
+
+    fn publish(&mut self, topic: &str, payload: &str) {
+        let mut body = (topic.len() as u16).to_be_bytes().to_vec();
+        body.extend_from_slice(topic.as_bytes());
+        body.extend_from_slice(payload.as_bytes());
+        self.send(0x30, &body);
+    }
+
+    // 应答客户端的报文，返回收到的发布 (主题, 内容, retain) 和订阅主题
+    fn handle(&mut self, published: &mut BTreeMap<String, (String, bool)>, subscribed: &mut Vec<String>) {
+        let packet = self.read_packet();
+        match packet.kind {
+            // CONNECT
+            1 => self.send(0x20, &[0, 0]),
+            // PUBLISH
+            3 => {
+                let topic_len = u16::from_be_bytes([packet.body[0], packet.body[1]]) as usize;
+                let topic = String::from_utf8(packet.body[2..2 + topic_len].to_vec()).unwrap();
+                let mut rest = &packet.body[2 + topic_len..];
+                if packet.flags & 0x06 != 0 {
+                    self.send(0x40, &rest[..2]);
+                    rest = &rest[2..];
+                }
+                let payload = String::from_utf8(rest.to_vec()).unwrap();
+                published.insert(topic, (payload, packet.flags & 0x01 != 0));
+            }
+            // SUBSCRIBE
+            8 => {
+                let topic_len = u16::from_be_bytes([packet.body[2], packet.body[3]]) as usize;
+                subscribed.push(String::from_utf8(packet.body[4..4 + topic_len].to_vec()).unwrap());
+                self.send(0x90, &[packet.body[0], packet.body[1], 1]);
+            }
+            // PINGREQ
+            12 => self.send(0xD0, &[]),
+            _ => {}
+        }
+    }
+}
+
+fn wait_until(what: &str, f: impl Fn() -> bool) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !f() {
+        assert!(Instant::now() < deadline, "等待超时: {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn mqtt_discovery_and_commands() {
+    let root = std::env::temp_dir().join(format!("nuc-fan-mqtt-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    let profile_dir = root.join("com.nuc.x15.fan.cyear.app").join("profiles");
+    fs::create_dir_all(&profile_dir).unwrap();
+    fs::write(profile_dir.join("quiet.json"), PROFILE).unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &root);
+    use_simulator();
+    ec_init();
+    device_init(ec_open().as_ref(), false).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let config = MqttConfig {
+        enabled: true,
+        host: "127.0.0.1".to_string(),
+        port: listener.local_addr().unwrap().port(),
+        client_id: "test_fan".to_string(),
+        base_topic: "test_fan".to_string(),
+        ..MqttConfig::default()
+    };
+    let controller = Controller::new();
+    start_mqtt(&controller, &config).unwrap();
+    let (stream, _) = listener.accept().unwrap();
+    stream.set_read_timeout(Some(Duration::from_secs(10))).unwrap();
+    let mut broker = BrokerStub { stream };
+
+    // 连接后先发布发现配置和上线状态，再订阅命令主题
+    let mut published = BTreeMap::new();
+    let mut subscribed = Vec::new();
+    while subscribed.len() < 2 {
+        broker.handle(&mut published, &mut subscribed);
+    }
+    assert_eq!(subscribed, ["test_fan/profile/set", "test_fan/fan/set"]);
+    assert_eq!(published["test_fan/availability"], ("online".to_string(), true));
+    let config_of = |topic: &str| -> Value {
+        let (payload, retain) = &published[topic];
+        assert!(retain, "{} 应为 retain", topic);
+        serde_json::from_str(payload).unwrap()
+    };
+    let sensors = published.keys().filter(|t| t.starts_with("homeassistant/sensor/test_fan/")).count();
+    assert_eq!(sensors, 11);
+    let cpu = config_of("homeassistant/sensor/test_fan/cpu_temp/config");
+    assert_eq!(cpu["state_topic"], json!("test_fan/state"));
+    assert_eq!(cpu["value_template"], json!("{{ value_json.cpu_temp }}"));
+    assert_eq!(cpu["device_class"], json!("temperature"));
+    assert_eq!(cpu["device"]["identifiers"], json!(["test_fan"]));
+    let select = config_of("homeassistant/select/test_fan/profile/config");
+    assert_eq!(select["command_topic"], json!("test_fan/profile/set"));
+    assert_eq!(select["options"], json!(["auto", "custom", "quiet"]));
+    let button = config_of("homeassistant/button/test_fan/fan_auto/config");
+    assert_eq!(button["command_topic"], json!("test_fan/fan/set"));
+    assert_eq!(button["payload_press"], json!("auto"));
+    config_of("homeassistant/binary_sensor/test_fan/running/config");
+
+    // 应答线程负责后续的 PINGREQ 等报文
+    let mut commands = BrokerStub { stream: broker.stream.try_clone().unwrap() };
+    thread::spawn(move || loop {
+        broker.handle(&mut BTreeMap::new(), &mut Vec::new());
+    });
+
+    commands.publish("test_fan/profile/set", "quiet");
+    wait_until("切换到 quiet", || controller.is_running() && *controller.fan.profile.lock().unwrap() == "quiet");
+    assert_eq!(fan_mode_state(), FanModeState::Controlling);
+
+    // 手动转速会停止曲线调速，占空比寄存器按 0~200 计
+    commands.publish("test_fan/fan/set", "30,40");
+    wait_until("手动转速", || !controller.is_running());
+    let ec = ec_open();
+    wait_until("写入占空比", || ec_read(ec.as_ref(), ADDR_LEFT_DUTY) == 60 && ec_read(ec.as_ref(), ADDR_RIGHT_DUTY) == 80);
+
+    commands.publish("test_fan/fan/set", r#"{"left": 50, "right": 70}"#);
+    wait_until("JSON 转速", || ec_read(ec.as_ref(), ADDR_LEFT_DUTY) == 100 && ec_read(ec.as_ref(), ADDR_RIGHT_DUTY) == 140);
+
+    commands.publish("test_fan/fan/set", "auto");
+    wait_until("交还自动控制", || fan_mode_state() == FanModeState::Released);
+    let _ = fs::remove_dir_all(&root);
+}
@@ -0,0 +1,42 @@
+#![cfg(unix)]
+
+use std::fs;
+use x15_71fan::modules::{
+    sim::{SimEc, SimState},
+    struct_set::{NvOc, PowerLimits},
+    tdp::{get_power_profile_dir, list_power_profiles, load_power_profile, read_nv_oc, read_power_limits, save_power_profile, write_power_limits},
+};
+
+const OC: NvOc = NvOc { enabled: true, ctgp: 120, cpu_power_target: 55, max_tgp: 150 };
+
+#[test]
+fn nv_oc_round_trips_through_power_profile() {
+    let root = std::env::temp_dir().join(format!("nuc-fan-power-profile-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    std::env::set_var("XDG_CONFIG_HOME", &root);
+    let ec = SimEc::new(SimState::new());
+
+    let limits = PowerLimits { pl1: 45, pl2: 90, pl4: 120, nv_oc: Some(OC), ..read_power_limits(&ec) };
+    save_power_profile("oc", &limits).unwrap();
+    assert_eq!(list_power_profiles(), ["oc"]);
+    let loaded = load_power_profile("oc").unwrap();
+    assert_eq!(loaded, limits);
+    write_power_limits(&ec, &loaded).unwrap().check().unwrap();
+    assert_eq!(read_power_limits(&ec), limits);
+
+    // 超出范围的 NV OC 不会保存
+    let invalid = PowerLimits { nv_oc: Some(NvOc { ctgp: 160, ..OC }), ..limits.clone() };
+    assert!(save_power_profile("invalid", &invalid).is_err());
+    assert_eq!(list_power_profiles(), ["oc"]);
+
+    // 旧的功耗配置没有 nv_oc，应用时保持 EC 中的 NV OC 不变
+    let mut old = serde_json::to_value(PowerLimits { pl1: 35, ..limits }).unwrap();
+    old.as_object_mut().unwrap().remove("nv_oc");
+    fs::write(get_power_profile_dir().join("old.json"), old.to_string()).unwrap();
+    let loaded = load_power_profile("old").unwrap();
+    assert_eq!(loaded.nv_oc, None);
+    write_power_limits(&ec, &loaded).unwrap().check().unwrap();
+    assert_eq!(read_power_limits(&ec).pl1, 35);
+    assert_eq!(read_nv_oc(&ec), OC);
+    let _ = fs::remove_dir_all(&root);
+}
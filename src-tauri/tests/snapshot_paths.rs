@@ -0,0 +1,41 @@
+#![cfg(unix)]
+
+use std::fs;
+use serde_json::{json, Value};
+use x15_71fan::modules::{
+    control::Controller,
+    ec::{ec_init, use_simulator},
+    rpc::{dispatch, INVALID_PARAMS},
+};
+
+// 守护进程只读写 exports 目录下的快照文件
+#[test]
+fn snapshot_files_stay_in_export_dir() {
+    let root = std::env::temp_dir().join(format!("nuc-fan-snapshot-test-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    std::env::set_var("XDG_CONFIG_HOME", &root);
+    use_simulator();
+    ec_init();
+    let controller = Controller::new();
+    let call = |method: &str, params: Value| dispatch(&controller, method, &params);
+    call("ec.snapshot.take", json!({ "name": "base", "ranges": [{ "start": 0x751, "len": 1 }] })).unwrap();
+
+    let outside = root.join("outside.json");
+    for path in [outside.to_string_lossy().to_string(), "../outside.json".to_string(), "a/../../outside.json".to_string()] {
+        let err = call("ec.snapshot.export", json!({ "name": "base", "path": path })).unwrap_err();
+        assert_eq!(err.code, INVALID_PARAMS, "{}", path);
+        let err = call("ec.snapshot.import", json!({ "path": path })).unwrap_err();
+        assert_eq!(err.code, INVALID_PARAMS, "{}", path);
+    }
+    assert!(!outside.exists());
+
+    let exported = call("ec.snapshot.export", json!({ "name": "base", "path": "ec/base.json" })).unwrap();
+    let path = root.join("com.nuc.x15.fan.cyear.app").join("exports").join("ec").join("base.json");
+    assert_eq!(exported["path"], json!(path.to_string_lossy()));
+    assert!(path.exists());
+    let imported = call("ec.snapshot.import", json!({ "path": "ec/base.json", "name": "copy" })).unwrap();
+    assert_eq!(imported["name"], json!("copy"));
+    assert_eq!(imported["registers"], call("ec.snapshot.get", json!({ "name": "base" })).unwrap()["registers"]);
+    let _ = fs::remove_dir_all(&root);
+}